@@ -0,0 +1,91 @@
+//! Decoding and encoding speed of a 1024x1024 texture in each format
+//!
+//! Run with `cargo bench`. Pre-allocating the decoded pixels and flipping them in place took the
+//! decoding times (median, in ms) from 9.1 to 8.2 for R5G5B5A1, 9.5 to 6.4 for R4G4B4A4, 10.4 to
+//! 8.6 for R5G6B5, 1.95 to 0.73 for R8G8B8A8, 7.8 to 4.3 for P8, 2.7 to 1.4 for L8 and 2.2 to 1.1
+//! for A8. Encoding already wrote into a pre-allocated buffer and stayed the same.
+//! Expanding the 16-bit pixels straight into the RGBA buffer then got those down to about 4 ms.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{Rgba, RgbaImage};
+use smb_tex::{decode_texture, encode_texture, ChannelOrder, TextureDataArgs, TextureFormat};
+
+const SIZE: u32 = 1024;
+
+/// Smooth gradients with a bit of alpha, roughly what the game's textures look like
+fn test_image() -> RgbaImage {
+    RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        Rgba([(x / 4) as u8, (y / 4) as u8, ((x + y) / 8) as u8, if (x / 64 + y / 64) % 2 == 0 { 0xFF } else { 0x80 }])
+    })
+}
+
+const FORMATS: [TextureFormat; 7] = [
+    TextureFormat::R5G5B5A1,
+    TextureFormat::R4G4B4A4,
+    TextureFormat::R5G6B5,
+    TextureFormat::R8G8B8A8,
+    TextureFormat::P8,
+    TextureFormat::L8,
+    TextureFormat::A8,
+];
+
+fn decode(c: &mut Criterion) {
+    let image = test_image();
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(SIZE as u64 * SIZE as u64));
+    for format in FORMATS {
+        let data = encode_texture(&image, format).unwrap();
+        let args = TextureDataArgs {
+            width: SIZE,
+            height: SIZE,
+            texture_format: format,
+            no_flip: false,
+            channel_order: ChannelOrder::Rgba,
+            row_stride: None,
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{:?}", format)), &data, |b, data| {
+            b.iter(|| decode_texture(black_box(data), &args).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let image = test_image();
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(SIZE as u64 * SIZE as u64));
+    for format in FORMATS {
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{:?}", format)), &image, |b, image| {
+            b.iter(|| encode_texture(black_box(image), format).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// The high resolution character textures are stored as R8G8B8A8, which has a faster path
+///
+/// Flipping the rows while copying them instead of in place afterwards took this from 3.1 to 1.3 ms.
+fn decode_large(c: &mut Criterion) {
+    const LARGE_SIZE: u32 = 2048;
+    let image = RgbaImage::from_fn(LARGE_SIZE, LARGE_SIZE, |x, y| Rgba([x as u8, y as u8, (x ^ y) as u8, 0xFF]));
+    let data = encode_texture(&image, TextureFormat::R8G8B8A8).unwrap();
+    let args = TextureDataArgs {
+        width: LARGE_SIZE,
+        height: LARGE_SIZE,
+        texture_format: TextureFormat::R8G8B8A8,
+        no_flip: false,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    let mut group = c.benchmark_group("decode_large");
+    group.throughput(Throughput::Elements(LARGE_SIZE as u64 * LARGE_SIZE as u64));
+    group.bench_function("R8G8B8A8", |b| b.iter(|| decode_texture(black_box(&data), &args).unwrap()));
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = decode, decode_large, encode
+}
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+//! Parsing arbitrary bytes must fail with an error, never panic
+//!
+//! Run with `cargo +nightly fuzz run read_texture_package` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = smb_tex::read_texture_package(data);
+    let _ = smb_tex::read_texture_package_with_endian(data, binrw::Endian::Big);
+});
@@ -0,0 +1,105 @@
+//! Analysis of the colors used by textures
+
+use std::collections::HashSet;
+use image::RgbaImage;
+use crate::error::Result;
+use crate::texture::{channel_bits, decode_texture, encode_texture, quantize, ChannelOrder, TextureDataArgs, TextureFormat};
+
+/// Textures with more distinct alpha values than this need the full 8 bits of alpha
+pub const MAX_SIMPLE_ALPHA_LEVELS: usize = 16;
+/// Share of distinct colors that may be merged by a 16-bit format before the texture is kept in R8G8B8A8
+pub const MAX_LOST_COLORS: f64 = 0.5;
+
+/// Color usage of a single texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorStats {
+    /// Some of the pixels are not fully opaque
+    pub translucent: bool,
+    /// Number of distinct RGBA colors
+    pub distinct_colors: usize,
+}
+
+pub fn color_stats(image: &RgbaImage) -> ColorStats {
+    let colors: HashSet<_> = image.pixels().map(|p| p.0).collect();
+    ColorStats {
+        translucent: colors.iter().any(|c| c[3] != 0xFF),
+        distinct_colors: colors.len(),
+    }
+}
+
+/// Picks the smallest format that keeps the texture looking close enough to the image
+///
+/// Fully opaque images get R5G6B5, images with only fully transparent and fully opaque pixels
+/// get R5G5B5A1 and images with at most [`MAX_SIMPLE_ALPHA_LEVELS`] alpha values get R4G4B4A4.
+/// Images with smoother alpha, or ones where the chosen 16-bit format would merge more than
+/// [`MAX_LOST_COLORS`] of the distinct colors (so fine gradients would band), get R8G8B8A8.
+pub fn choose_format(image: &RgbaImage) -> TextureFormat {
+    let colors: HashSet<_> = image.pixels().map(|p| p.0).collect();
+    let alphas: HashSet<_> = colors.iter().map(|c| c[3]).collect();
+
+    let format = if alphas.iter().all(|&a| a == 0xFF) {
+        TextureFormat::R5G6B5
+    } else if alphas.iter().all(|&a| a == 0 || a == 0xFF) {
+        TextureFormat::R5G5B5A1
+    } else if alphas.len() <= MAX_SIMPLE_ALPHA_LEVELS {
+        TextureFormat::R4G4B4A4
+    } else {
+        return TextureFormat::R8G8B8A8;
+    };
+
+    let bits = channel_bits(format);
+    let quantized: HashSet<_> = colors.iter()
+        .map(|c| [0, 1, 2, 3].map(|i| quantize(c[i], bits[i])))
+        .collect();
+    if (quantized.len() as f64) < colors.len() as f64 * (1.0 - MAX_LOST_COLORS) {
+        TextureFormat::R8G8B8A8
+    } else {
+        format
+    }
+}
+
+/// Detail of an image lost by storing it in a format, see [`precision_loss`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionLoss {
+    /// Number of distinct alpha values in the image
+    pub alpha_levels: usize,
+    /// Number of pixels whose alpha is changed by the format
+    pub changed_alpha: usize,
+    /// Number of distinct colors in the image, ignoring alpha
+    pub distinct_colors: usize,
+    /// Number of distinct colors left after storing the image
+    pub stored_colors: usize,
+}
+
+impl PrecisionLoss {
+    /// Some of the pixels end up with a different alpha, which shows as changed edges
+    pub fn loses_alpha(&self) -> bool {
+        self.changed_alpha > 0
+    }
+
+    /// More than [`MAX_LOST_COLORS`] of the distinct colors are merged, so gradients will band
+    pub fn loses_colors(&self) -> bool {
+        (self.stored_colors as f64) < self.distinct_colors as f64 * (1.0 - MAX_LOST_COLORS)
+    }
+}
+
+/// Compares an image with what is left of it after encoding it in the format and decoding it again
+pub fn precision_loss(image: &RgbaImage, format: TextureFormat) -> Result<PrecisionLoss> {
+    let args = TextureDataArgs {
+        width: image.width(),
+        height: image.height(),
+        texture_format: format,
+        no_flip: false,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    let stored = decode_texture(&encode_texture(image, format)?, &args)?;
+
+    let rgb = |p: &image::Rgba<u8>| [p[0], p[1], p[2]];
+    Ok(PrecisionLoss {
+        alpha_levels: image.pixels().map(|p| p[3]).collect::<HashSet<_>>().len(),
+        changed_alpha: image.pixels().zip(stored.pixels()).filter(|(a, b)| a[3] != b[3]).count(),
+        distinct_colors: image.pixels().map(rgb).collect::<HashSet<_>>().len(),
+        stored_colors: stored.pixels().map(rgb).collect::<HashSet<_>>().len(),
+    })
+}
@@ -0,0 +1,104 @@
+//! Combining many small textures into a single atlas texture
+//!
+//! Each texture in a package has a header and costs a GPU state change when drawn, so packing tiny
+//! ones (like UI elements) into one texture can help. The positions of the original textures in the
+//! atlas are returned so they can be written out for whatever code samples the atlas.
+
+use image::{GenericImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::texture::{Texture, TextureFormat, TextureMeta, TexturePackage};
+
+/// Position of an image in an atlas, in pixels from the top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Position of a texture of the original package in the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+    pub id: u32,
+    #[serde(flatten)]
+    pub rect: Rect,
+}
+
+/// Packs the images into a single atlas, returning it and the position of each image in input order
+///
+/// Uses a simple shelf packer: images are placed left to right in rows, tallest first, and a new
+/// row is started when one doesn't fit. Both dimensions of the atlas are powers of two.
+pub fn pack_atlas(images: &[RgbaImage]) -> (RgbaImage, Vec<Rect>) {
+    if images.is_empty() {
+        return (RgbaImage::new(0, 0), Vec::new());
+    }
+
+    let area: u64 = images.iter().map(|i| i.width() as u64 * i.height() as u64).sum();
+    let widest = images.iter().map(|i| i.width()).max().unwrap_or(0);
+    let width = widest.max((area as f64).sqrt().ceil() as u32).next_power_of_two();
+
+    let mut order: Vec<_> = (0..images.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(images[i].height()));
+
+    let mut rects = vec![Rect { x: 0, y: 0, width: 0, height: 0 }; images.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for i in order {
+        let image = &images[i];
+        if x + image.width() > width {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        rects[i] = Rect {
+            x,
+            y,
+            width: image.width(),
+            height: image.height(),
+        };
+        x += image.width();
+        shelf_height = shelf_height.max(image.height());
+    }
+    let height = (y + shelf_height).next_power_of_two();
+
+    let mut atlas = RgbaImage::new(width, height);
+    for (image, rect) in images.iter().zip(rects.iter()) {
+        atlas.copy_from(image, rect.x, rect.y)
+            .expect("the packer keeps images inside the atlas");
+    }
+    (atlas, rects)
+}
+
+/// Replaces all the textures of a package with a single atlas texture with the given id
+///
+/// The meta of the atlas is taken from the first texture, with a single mip level. If the textures
+/// use different formats, the atlas is stored as R8G8B8A8 so none of them lose precision.
+pub fn atlas_package(package: &TexturePackage, id: u32) -> (TexturePackage, Vec<AtlasEntry>) {
+    let first = match package.textures.first() {
+        Some(texture) => texture,
+        None => return (TexturePackage { textures_ptr: package.textures_ptr, textures: Vec::new() }, Vec::new()),
+    };
+
+    let images: Vec<_> = package.textures.iter().map(|t| t.data.clone()).collect();
+    let (atlas, rects) = pack_atlas(&images);
+    let entries = package.textures.iter().zip(rects)
+        .map(|(texture, rect)| AtlasEntry { id: texture.meta.id, rect })
+        .collect();
+
+    let same_format = package.textures.iter().all(|t| t.meta.texture_format == first.meta.texture_format);
+    let meta = TextureMeta {
+        id,
+        texture_format: if same_format { first.meta.texture_format } else { TextureFormat::R8G8B8A8 },
+        order: None,
+        mip_count: 1,
+        image_hash: None,
+        ..first.meta
+    };
+    let texture = Texture {
+        meta,
+        data: atlas,
+        mips: Vec::new(),
+        raw: None,
+    };
+    (TexturePackage { textures_ptr: package.textures_ptr, textures: vec![texture] }, entries)
+}
@@ -0,0 +1,124 @@
+//! Writing textures as uncompressed DDS files, keeping their packed pixel formats
+
+use binrw::{BinWrite, Endian};
+use crate::error::{Result, TextureError};
+use crate::texture::{to_little_endian, TextureFormat, TextureHeader};
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_RGB: u32 = 0x40;
+
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+
+#[derive(BinWrite, Debug)]
+#[bw(magic = b"DDS ")]
+struct DdsHeader {
+    size: u32,
+    flags: u32,
+    height: u32,
+    width: u32,
+    pitch_or_linear_size: u32,
+    depth: u32,
+    mip_map_count: u32,
+    reserved1: [u32; 11],
+    pixel_format: DdsPixelFormat,
+    caps: u32,
+    caps2: u32,
+    caps3: u32,
+    caps4: u32,
+    reserved2: u32,
+}
+
+#[derive(BinWrite, Debug)]
+struct DdsPixelFormat {
+    size: u32,
+    flags: u32,
+    four_cc: u32,
+    rgb_bit_count: u32,
+    r_bit_mask: u32,
+    g_bit_mask: u32,
+    b_bit_mask: u32,
+    a_bit_mask: u32,
+}
+
+impl DdsPixelFormat {
+    fn new(format: TextureFormat) -> Self {
+        // (bit count, r, g, b, a) masks for the little-endian pixel values
+        let (rgb_bit_count, r, g, b, a) = match format {
+            TextureFormat::R5G5B5A1 => (16, 0xF800, 0x07C0, 0x003E, 0x0001),
+            TextureFormat::R4G4B4A4 => (16, 0xF000, 0x0F00, 0x00F0, 0x000F),
+            TextureFormat::R5G6B5 => (16, 0xF800, 0x07E0, 0x001F, 0x0000),
+            TextureFormat::R8G8B8A8 => (32, 0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000),
+            TextureFormat::P8 | TextureFormat::L8 | TextureFormat::A8 => unreachable!("rejected by write_dds"),
+        };
+
+        Self {
+            size: 32,
+            flags: if a != 0 { DDPF_RGB | DDPF_ALPHAPIXELS } else { DDPF_RGB },
+            four_cc: 0,
+            rgb_bit_count,
+            r_bit_mask: r,
+            g_bit_mask: g,
+            b_bit_mask: b,
+            a_bit_mask: a,
+        }
+    }
+}
+
+/// Wraps the raw pixel data of a texture (as stored in the package) into a DDS file
+///
+/// The pixel values are kept as is, only the rows are reordered to be top-down as DDS expects
+/// (unless `no_flip` says they already are) and 16-bit pixels from big-endian packages are byte-swapped,
+/// since DDS is always little-endian.
+pub fn write_dds(header: &TextureHeader, data: &[u8], endian: Endian, no_flip: bool) -> Result<Vec<u8>> {
+    if matches!(header.texture_format, TextureFormat::P8 | TextureFormat::L8 | TextureFormat::A8) {
+        return Err(TextureError::UnsupportedFormat {
+            format: header.texture_format,
+            container: "DDS",
+        });
+    }
+    let size = header.data_size()? as usize;
+    if data.len() != size {
+        return Err(TextureError::TruncatedData {
+            expected: size as u64,
+            actual: data.len() as u64,
+        });
+    }
+    let pitch = if header.height == 0 { 0 } else { size / header.height as usize };
+
+    let dds_header = DdsHeader {
+        size: 124,
+        flags: DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PITCH | DDSD_PIXELFORMAT,
+        height: header.height,
+        width: header.width,
+        pitch_or_linear_size: pitch as u32,
+        depth: 0,
+        mip_map_count: 0,
+        reserved1: [0; 11],
+        pixel_format: DdsPixelFormat::new(header.texture_format),
+        caps: DDSCAPS_TEXTURE,
+        caps2: 0,
+        caps3: 0,
+        caps4: 0,
+        reserved2: 0,
+    };
+
+    let mut buf = Vec::with_capacity(128 + size);
+    dds_header.write_le(&mut std::io::Cursor::new(&mut buf))?;
+    let data = to_little_endian(data, header.texture_format, endian);
+    // the textures are stored upside-down, see `decode_texture`
+    if no_flip {
+        buf.extend_from_slice(&data);
+    } else if pitch != 0 {
+        for row in data.chunks_exact(pitch).rev() {
+            buf.extend_from_slice(row);
+        }
+    }
+
+    Ok(buf)
+}
@@ -0,0 +1,115 @@
+//! Comparing the textures of two packages
+
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use crate::error::Result;
+use crate::texture::{decode_texture, read_texture_headers, TextureEntry, TextureFormat};
+
+/// A difference between the textures with the same id in two packages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Difference {
+    OnlyInA { id: u32 },
+    OnlyInB { id: u32 },
+    /// The dimensions or the format changed
+    Layout { id: u32, a: Layout, b: Layout },
+    /// Some of the decoded pixels differ, only reported for textures with the same dimensions
+    Pixels { id: u32, differing: u64, total: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Layout {
+    pub width: u32,
+    pub height: u32,
+    pub texture_format: TextureFormat,
+}
+
+impl Layout {
+    fn of(entry: &TextureEntry) -> Self {
+        Self {
+            width: entry.header.width,
+            height: entry.header.height,
+            texture_format: entry.header.texture_format,
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} {:?}", self.width, self.height, self.texture_format)
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Difference::OnlyInA { id } => write!(f, "only in a: {:08x}", id),
+            Difference::OnlyInB { id } => write!(f, "only in b: {:08x}", id),
+            Difference::Layout { id, a, b } => write!(f, "{:08x}: {} -> {}", id, a, b),
+            Difference::Pixels { id, differing, total } => write!(
+                f,
+                "{:08x}: {:.2}% of pixels differ ({}/{})",
+                id, differing as f64 * 100.0 / total as f64, differing, total
+            ),
+        }
+    }
+}
+
+/// Compares the textures of two packages by id, returning the differences sorted by id
+///
+/// Only the first texture with each id is compared, use [`crate::verify`] to find duplicates.
+pub fn diff_packages(a: &[u8], b: &[u8]) -> Result<Vec<Difference>> {
+    let a_headers = read_texture_headers(a)?;
+    let b_headers = read_texture_headers(b)?;
+    let mut a_entries = BTreeMap::new();
+    for entry in a_headers.entries.iter() {
+        a_entries.entry(entry.header.id).or_insert(entry);
+    }
+    let mut b_entries = BTreeMap::new();
+    for entry in b_headers.entries.iter() {
+        b_entries.entry(entry.header.id).or_insert(entry);
+    }
+
+    let mut differences = Vec::new();
+    for &id in a_entries.keys() {
+        if !b_entries.contains_key(&id) {
+            differences.push(Difference::OnlyInA { id });
+        }
+    }
+    for &id in b_entries.keys() {
+        if !a_entries.contains_key(&id) {
+            differences.push(Difference::OnlyInB { id });
+        }
+    }
+
+    for (&id, a_entry) in a_entries.iter() {
+        let b_entry = match b_entries.get(&id) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let (a_layout, b_layout) = (Layout::of(a_entry), Layout::of(b_entry));
+        if a_layout != b_layout {
+            differences.push(Difference::Layout { id, a: a_layout, b: b_layout });
+        }
+        if (a_layout.width, a_layout.height) != (b_layout.width, b_layout.height) {
+            continue;
+        }
+
+        let a_image = decode_texture(a_entry.raw_data(a)?, &a_entry.header.data_args())?;
+        let b_image = decode_texture(b_entry.raw_data(b)?, &b_entry.header.data_args())?;
+        let differing = a_image.pixels().zip(b_image.pixels()).filter(|(a, b)| a != b).count() as u64;
+        if differing != 0 {
+            let total = a_layout.width as u64 * a_layout.height as u64;
+            differences.push(Difference::Pixels { id, differing, total });
+        }
+    }
+
+    differences.sort_by_key(|d| match *d {
+        Difference::OnlyInA { id }
+        | Difference::OnlyInB { id }
+        | Difference::Layout { id, .. }
+        | Difference::Pixels { id, .. } => id,
+    });
+    Ok(differences)
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use crate::texture::TextureFormat;
+
+/// Errors returned by the library
+#[derive(thiserror::Error, Debug)]
+pub enum TextureError {
+    /// There is less (or more) pixel data than the texture dimensions call for
+    #[error("Expected {expected} bytes of texture data, got {actual}")]
+    TruncatedData { expected: u64, actual: u64 },
+    /// Decoding produced a different number of pixels than the texture dimensions call for
+    #[error("Decoded {actual} bytes of pixels for a {width}x{height} image, expected {expected}")]
+    PixelBufferSize { width: u32, height: u32, expected: u64, actual: u64 },
+    /// The pixel data of a texture does not fit into the 32-bit sizes used by the package format
+    #[error("Data size of a {width}x{height} {format:?} texture does not fit into 32 bits")]
+    SizeOverflow { width: u32, height: u32, format: TextureFormat },
+    /// The package as a whole does not fit into the 32-bit offsets used by the package format
+    #[error("Package data does not fit into 32-bit offsets")]
+    PackageTooLarge,
+    /// The texture headers of a package would overlap the texture count and the pointer to them
+    #[error("Texture headers can't start at offset {0:#x}, inside the package header")]
+    InvalidTexturesPtr(u32),
+    /// The data pointer of a texture header is zero or points past the end of the file
+    #[error(
+        "Texture {id:08x} has data offset {offset:#x}, {}",
+        if *.offset == 0 { "pointing at the package header".to_string() } else { format!("past the end of the {}-byte file", .file_size) }
+    )]
+    InvalidDataOffset { id: u32, offset: u32, file_size: u64 },
+    /// The format field of a texture header has a value this library doesn't know how to decode
+    #[error("Texture {id:08x} (header at offset {offset:#x}) has unknown format {format}, it may be one that isn't supported yet")]
+    UnknownFormat { id: u32, offset: u64, format: u32 },
+    #[error("Row stride of {stride} bytes is smaller than a row of {row_size} bytes")]
+    InvalidRowStride { stride: u32, row_size: u64 },
+    #[error("Expected a {}x{} image, got {}x{}", expected.0, expected.1, actual.0, actual.1)]
+    DimensionMismatch { expected: (u32, u32), actual: (u32, u32) },
+    #[error("A {width}x{height} texture can have between 1 and {max} mip levels, {count} requested")]
+    InvalidMipCount { width: u32, height: u32, count: u32, max: u32 },
+    #[error("Missing meta file for texture {}", .0.display())]
+    MissingMeta(PathBuf),
+    #[error("Texture {} has both a .json and a .toml meta file, remove one of them", .0.display())]
+    AmbiguousMeta(PathBuf),
+    #[error("Invalid meta file {}", path.display())]
+    InvalidMeta {
+        path: PathBuf,
+        #[source]
+        source: MetaError,
+    },
+    #[error("File {} listed in the manifest does not exist", .0.display())]
+    MissingManifestFile(PathBuf),
+    #[error("{format:?} textures can't be stored in {container} files")]
+    UnsupportedFormat { format: TextureFormat, container: &'static str },
+    #[error("Invalid KTX2 file: {0}")]
+    InvalidKtx2(&'static str),
+    #[error("Texture {0:08x} has zero width or height")]
+    EmptyTexture(u32),
+    #[error("Texture {id:08x} is {width}x{height}, larger than the maximum of {max}")]
+    TextureTooLarge {
+        id: u32,
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    #[error("Texture {id:08x} is {width}x{height}, which is not a power of two")]
+    NotPowerOfTwo {
+        id: u32,
+        width: u32,
+        height: u32,
+    },
+    #[error("No texture with id {0:08x} in the package")]
+    TextureNotFound(u32),
+    #[error("Invalid name map: {0}")]
+    InvalidNameMap(String),
+    #[error("Invalid name map file {}", path.display())]
+    InvalidNameMapFile {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Invalid table of contents {} of a raw dump", path.display())]
+    InvalidRawToc {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Invalid name template: {0}")]
+    InvalidNameTemplate(String),
+    #[error("Duplicate texture ids: {0}")]
+    DuplicateIds(String),
+    /// Loading a texture from a directory failed
+    #[error("Failed to load texture {}", path.display())]
+    Load {
+        path: PathBuf,
+        #[source]
+        source: Box<TextureError>,
+    },
+    /// The package structure could not be parsed
+    #[error("Failed to parse texture package")]
+    Parse(#[source] binrw::Error),
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error(transparent)]
+    Png(#[from] png::EncodingError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = TextureError> = std::result::Result<T, E>;
+
+/// Error parsing a meta file, from the parser of the format it is written in
+///
+/// Both keep the position of the problem, see [`serde_json::Error::line`] and [`toml::de::Error::span`].
+#[derive(thiserror::Error, Debug)]
+pub enum MetaError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+}
+
+impl From<binrw::Error> for TextureError {
+    fn from(err: binrw::Error) -> Self {
+        match err {
+            // errors raised by our own parsing functions are passed through binrw as custom ones
+            binrw::Error::Backtrace(backtrace) => Self::from(*backtrace.error),
+            binrw::Error::Custom { pos, err } => match err.downcast::<TextureError>() {
+                Ok(err) => *err,
+                Err(err) => Self::Parse(binrw::Error::Custom { pos, err }),
+            },
+            binrw::Error::Io(err) => Self::Io(err),
+            err => Self::Parse(err),
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Writing and reading textures as KTX2 files, keeping their packed pixel formats
+//!
+//! All the package formats have a matching uncompressed VkFormat, so the pixel data is stored as is,
+//! with the rows reordered to be top-down.
+
+use std::io::Cursor;
+use binrw::{BinRead, BinWrite, Endian};
+use crate::error::{Result, TextureError};
+use crate::texture::{data_size, to_little_endian, ChannelOrder, TextureDataArgs, TextureFormat, TextureHeader};
+
+const VK_FORMAT_R4G4B4A4_UNORM_PACK16: u32 = 2;
+const VK_FORMAT_R5G6B5_UNORM_PACK16: u32 = 4;
+const VK_FORMAT_R5G5B5A1_UNORM_PACK16: u32 = 6;
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+
+const KHR_DF_CHANNEL_R: u8 = 0;
+const KHR_DF_CHANNEL_G: u8 = 1;
+const KHR_DF_CHANNEL_B: u8 = 2;
+const KHR_DF_CHANNEL_A: u8 = 15;
+
+/// Size of the header, the index and a single level index entry
+const HEADER_SIZE: u32 = 104;
+
+#[derive(BinRead, BinWrite, Debug)]
+#[brw(little, magic = b"\xABKTX 20\xBB\r\n\x1A\n")]
+struct Ktx2Header {
+    vk_format: u32,
+    type_size: u32,
+    pixel_width: u32,
+    pixel_height: u32,
+    pixel_depth: u32,
+    layer_count: u32,
+    face_count: u32,
+    level_count: u32,
+    supercompression_scheme: u32,
+    dfd_byte_offset: u32,
+    dfd_byte_length: u32,
+    kvd_byte_offset: u32,
+    kvd_byte_length: u32,
+    sgd_byte_offset: u64,
+    sgd_byte_length: u64,
+    /// Only the base level is written, so this is the whole level index
+    level: Ktx2Level,
+}
+
+#[derive(BinRead, BinWrite, Debug)]
+struct Ktx2Level {
+    byte_offset: u64,
+    byte_length: u64,
+    uncompressed_byte_length: u64,
+}
+
+fn vk_format(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::R5G5B5A1 => VK_FORMAT_R5G5B5A1_UNORM_PACK16,
+        TextureFormat::R4G4B4A4 => VK_FORMAT_R4G4B4A4_UNORM_PACK16,
+        TextureFormat::R5G6B5 => VK_FORMAT_R5G6B5_UNORM_PACK16,
+        TextureFormat::R8G8B8A8 => VK_FORMAT_R8G8B8A8_UNORM,
+        TextureFormat::P8 | TextureFormat::L8 | TextureFormat::A8 => unreachable!("rejected by write_ktx2"),
+    }
+}
+
+fn texture_format(vk_format: u32) -> Option<TextureFormat> {
+    match vk_format {
+        VK_FORMAT_R5G5B5A1_UNORM_PACK16 => Some(TextureFormat::R5G5B5A1),
+        VK_FORMAT_R4G4B4A4_UNORM_PACK16 => Some(TextureFormat::R4G4B4A4),
+        VK_FORMAT_R5G6B5_UNORM_PACK16 => Some(TextureFormat::R5G6B5),
+        VK_FORMAT_R8G8B8A8_UNORM => Some(TextureFormat::R8G8B8A8),
+        _ => None,
+    }
+}
+
+/// Builds the data format descriptor, a single basic descriptor block
+fn data_format_descriptor(format: TextureFormat) -> Vec<u8> {
+    // (channel, bit offset, bit length) of the samples, for the little-endian pixel values
+    let (texel_size, samples): (u8, &[(u8, u16, u8)]) = match format {
+        TextureFormat::R5G5B5A1 => (2, &[(KHR_DF_CHANNEL_A, 0, 1), (KHR_DF_CHANNEL_B, 1, 5), (KHR_DF_CHANNEL_G, 6, 5), (KHR_DF_CHANNEL_R, 11, 5)]),
+        TextureFormat::R4G4B4A4 => (2, &[(KHR_DF_CHANNEL_A, 0, 4), (KHR_DF_CHANNEL_B, 4, 4), (KHR_DF_CHANNEL_G, 8, 4), (KHR_DF_CHANNEL_R, 12, 4)]),
+        TextureFormat::R5G6B5 => (2, &[(KHR_DF_CHANNEL_B, 0, 5), (KHR_DF_CHANNEL_G, 5, 6), (KHR_DF_CHANNEL_R, 11, 5)]),
+        TextureFormat::R8G8B8A8 => (4, &[(KHR_DF_CHANNEL_R, 0, 8), (KHR_DF_CHANNEL_G, 8, 8), (KHR_DF_CHANNEL_B, 16, 8), (KHR_DF_CHANNEL_A, 24, 8)]),
+        TextureFormat::P8 | TextureFormat::L8 | TextureFormat::A8 => unreachable!("rejected by write_ktx2"),
+    };
+    let block_size = 24 + 16 * samples.len() as u32;
+
+    let mut dfd = Vec::new();
+    dfd.extend_from_slice(&(4 + block_size).to_le_bytes());
+    // vendor id and descriptor type are both 0 (Khronos, basic)
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&2u16.to_le_bytes());
+    dfd.extend_from_slice(&(block_size as u16).to_le_bytes());
+    dfd.extend_from_slice(&[KHR_DF_MODEL_RGBSDA, KHR_DF_PRIMARIES_BT709, KHR_DF_TRANSFER_LINEAR, 0]);
+    // texel block dimensions, stored minus one
+    dfd.extend_from_slice(&[0; 4]);
+    dfd.extend_from_slice(&[texel_size, 0, 0, 0, 0, 0, 0, 0]);
+    for &(channel, offset, length) in samples {
+        dfd.extend_from_slice(&offset.to_le_bytes());
+        dfd.extend_from_slice(&[length - 1, channel]);
+        dfd.extend_from_slice(&[0; 4]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&((1u32 << length) - 1).to_le_bytes());
+    }
+    dfd
+}
+
+/// Builds the key/value data, holding only the writer name
+fn key_value_data() -> Vec<u8> {
+    let entry = format!("KTXwriter\0smb_tex {}\0", env!("CARGO_PKG_VERSION"));
+    let mut kvd = Vec::new();
+    kvd.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+    kvd.extend_from_slice(entry.as_bytes());
+    kvd.resize((kvd.len() + 3) & !3, 0);
+    kvd
+}
+
+/// Wraps the raw pixel data of a texture (as stored in the package) into a KTX2 file
+///
+/// Like [`crate::dds::write_dds`], the rows are reordered to be top-down (unless `no_flip` says
+/// they already are) and 16-bit pixels from big-endian packages are byte-swapped, since KTX2 is
+/// always little-endian.
+pub fn write_ktx2(header: &TextureHeader, data: &[u8], endian: Endian, no_flip: bool) -> Result<Vec<u8>> {
+    if matches!(header.texture_format, TextureFormat::P8 | TextureFormat::L8 | TextureFormat::A8) {
+        return Err(TextureError::UnsupportedFormat {
+            format: header.texture_format,
+            container: "KTX2",
+        });
+    }
+    let size = header.data_size()? as usize;
+    if data.len() != size {
+        return Err(TextureError::TruncatedData {
+            expected: size as u64,
+            actual: data.len() as u64,
+        });
+    }
+    let pitch = if header.height == 0 { 0 } else { size / header.height as usize };
+
+    let dfd = data_format_descriptor(header.texture_format);
+    let kvd = key_value_data();
+    let dfd_offset = HEADER_SIZE;
+    let kvd_offset = dfd_offset + dfd.len() as u32;
+    // both are multiples of 4, which is all the alignment the uncompressed formats need
+    let data_offset = kvd_offset + kvd.len() as u32;
+
+    let ktx2_header = Ktx2Header {
+        vk_format: vk_format(header.texture_format),
+        type_size: if header.texture_format == TextureFormat::R8G8B8A8 { 1 } else { 2 },
+        pixel_width: header.width,
+        pixel_height: header.height,
+        pixel_depth: 0,
+        layer_count: 0,
+        face_count: 1,
+        level_count: 1,
+        supercompression_scheme: 0,
+        dfd_byte_offset: dfd_offset,
+        dfd_byte_length: dfd.len() as u32,
+        kvd_byte_offset: kvd_offset,
+        kvd_byte_length: kvd.len() as u32,
+        sgd_byte_offset: 0,
+        sgd_byte_length: 0,
+        level: Ktx2Level {
+            byte_offset: data_offset as u64,
+            byte_length: size as u64,
+            uncompressed_byte_length: size as u64,
+        },
+    };
+
+    let mut buf = Vec::with_capacity(data_offset as usize + size);
+    ktx2_header.write_le(&mut Cursor::new(&mut buf))?;
+    buf.extend_from_slice(&dfd);
+    buf.extend_from_slice(&kvd);
+    let data = to_little_endian(data, header.texture_format, endian);
+    // the textures are stored upside-down, see `decode_texture`
+    if no_flip {
+        buf.extend_from_slice(&data);
+    } else if pitch != 0 {
+        for row in data.chunks_exact(pitch).rev() {
+            buf.extend_from_slice(row);
+        }
+    }
+
+    Ok(buf)
+}
+
+/// Reads the base level of a KTX2 file written by [`write_ktx2`]
+///
+/// Returns the pixel data the way it is stored in a package: little-endian and bottom row first
+/// (or top row first with `no_flip`).
+pub fn read_ktx2(data: &[u8], no_flip: bool) -> Result<(TextureDataArgs, Vec<u8>)> {
+    let header = Ktx2Header::read_le(&mut Cursor::new(data))?;
+    let texture_format = texture_format(header.vk_format)
+        .ok_or(TextureError::InvalidKtx2("unsupported vkFormat"))?;
+    if header.supercompression_scheme != 0 {
+        return Err(TextureError::InvalidKtx2("supercompressed files are not supported"));
+    }
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count != 1 {
+        return Err(TextureError::InvalidKtx2("only 2D textures are supported"));
+    }
+
+    let size = data_size(texture_format, header.pixel_width, header.pixel_height)? as usize;
+    let offset = usize::try_from(header.level.byte_offset).unwrap_or(usize::MAX);
+    let level = offset.checked_add(size)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(TextureError::TruncatedData {
+            expected: size as u64,
+            actual: data.len().saturating_sub(offset) as u64,
+        })?;
+    let pitch = if header.pixel_height == 0 { 0 } else { size / header.pixel_height as usize };
+    let mut pixels = Vec::with_capacity(size);
+    if no_flip {
+        pixels.extend_from_slice(level);
+    } else if pitch != 0 {
+        for row in level.chunks_exact(pitch).rev() {
+            pixels.extend_from_slice(row);
+        }
+    }
+
+    let args = TextureDataArgs {
+        width: header.pixel_width,
+        height: header.pixel_height,
+        texture_format,
+        no_flip,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    Ok((args, pixels))
+}
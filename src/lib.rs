@@ -0,0 +1,30 @@
+pub mod analysis;
+pub mod atlas;
+pub mod dds;
+pub mod diff;
+pub mod error;
+pub mod ktx2;
+pub mod meta;
+pub mod mip;
+pub mod montage;
+pub mod names;
+#[cfg(feature = "package-serde")]
+mod package_serde;
+pub mod palette;
+pub mod raw;
+pub mod texture;
+pub mod verify;
+
+pub use error::{MetaError, TextureError};
+pub use texture::{
+    add_texture, decode_texture, decode_texture_with_endian, encode_texture,
+    aligned_row_stride, encode_texture_with_options, load_texture, load_texture_with_endian, load_texture_with_options, read_manifest,
+    read_texture_headers, read_texture_headers_from, read_texture_headers_from_with_endian,
+    read_texture_headers_with_endian, read_texture_package, read_texture_package_from,
+    read_texture_package_from_with_endian, read_texture_package_from_with_options, read_texture_package_with_endian,
+    read_texture_package_with_options, remove_textures,
+    repack_package, replace_texture, write_texture_package, write_texture_package_with_options, ChannelOrder, DirectoryOptions,
+    EncodeOptions, ManifestEntry, RawTextureData, ReadOptions, RepackOptions, Resize, Texture, TextureDataArgs, TextureFlags,
+    TextureFormat, TextureHeader, TextureMeta, TexturePackage, TexturePackageBuilder, WrapMode, WriteOptions,
+    PACKAGE_HEADER_SIZE, TEX_HEADER_SIZE,
+};
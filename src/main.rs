@@ -1,11 +1,44 @@
-use std::path::PathBuf;
-use clap::Parser;
-use crate::texture::TexturePackage;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use anyhow::{bail, Context, Result};
+use binrw::Endian;
+use clap::{Parser, ValueEnum};
+use image::imageops::FilterType;
+use image::{ImageFormat, RgbaImage};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use smb_tex::meta::MetaFormat;
+use smb_tex::mip::MipGamma;
+use smb_tex::names::{NameHash, NameMap, NameTemplate};
+use smb_tex::texture::{
+    self, channel_bits, ChannelOrder, DirectoryOptions, EncodeOptions, Resize, Texture, TextureDataArgs, TextureEntry,
+    TextureFormat, TextureMeta, TexturePackage, WriteOptions,
+};
 
-mod texture;
+/// Set by `--quiet`, which silences the warnings and the progress bar
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a warning to stderr, unless `--quiet` was passed
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            eprintln!("warning: {}", format_args!($($arg)*));
+        }
+    };
+}
 
 #[derive(clap::Parser, Debug)]
 struct Cli {
+    #[clap(long, global = true, default_value_t = 0)]
+    /// Number of threads to decode and encode textures with, 0 uses all cores
+    threads: usize,
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    /// Log what is read and written to stderr, repeat for more detail
+    verbose: u8,
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    /// Don't print warnings or the progress bar, only errors and the requested output
+    quiet: bool,
     #[clap(subcommand)]
     command: CliCommand,
 }
@@ -18,49 +51,1488 @@ enum CliCommand {
         path: PathBuf,
         /// Path to the output directory
         result: PathBuf,
+        #[clap(long = "id", value_name = "ID", value_parser = parse_hex_id)]
+        /// Only extract textures with the given hex id (can be repeated)
+        ids: Vec<u32>,
+        #[clap(long, visible_alias = "output-format", value_enum, default_value_t = ExtractFormat::Png)]
+        /// File format to extract the textures to
+        format: ExtractFormat,
+        #[clap(long, value_enum, default_value_t = CliEndian::Le)]
+        /// Byte order of the tpg file (the iOS version uses little-endian)
+        endian: CliEndian,
+        #[clap(long)]
+        /// Also save the raw texture data, so that unedited textures are packed back bit-perfect
+        lossless: bool,
+        #[clap(long)]
+        /// Don't flip the textures vertically, for packages storing them top-down
+        no_flip: bool,
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        /// The rows of the textures start at multiples of N bytes, for variants padding them (128 on some consoles)
+        row_align: u32,
+        #[clap(long)]
+        /// JSON file mapping hex texture ids to the file names to use for them
+        name_map: Option<PathBuf>,
+        #[clap(long, value_name = "TEMPLATE", value_parser = parse_name_template, default_value = "{id:08x}")]
+        /// File names of the textures not in the name map, with {id}, {id:x}, {id:08x}, {index}, {index:03} and {format} placeholders
+        name_template: NameTemplate,
+        #[clap(long)]
+        /// Save the decoded images with premultiplied alpha
+        premultiply: bool,
+        #[clap(long)]
+        /// Write the .json files with named fields and wrap modes instead of the raw header values
+        pretty_meta: bool,
+        #[clap(long, value_enum, default_value_t = MetaFormat::Json)]
+        /// File format of the meta files next to the images
+        meta_format: MetaFormat,
+        #[clap(long)]
+        /// Save textures in 16-bit formats as 16-bit PNGs holding the exact packed values
+        keep_bits: bool,
+        #[clap(long, value_enum, default_value_t = ChannelOrder::Rgba)]
+        /// Order the color channels are stored in, bgra for packages with red and blue swapped
+        channel_order: ChannelOrder,
+        #[clap(long, conflicts_with = "no_clobber")]
+        /// Replace files that already exist in the output directory, instead of failing
+        overwrite: bool,
+        #[clap(long)]
+        /// Skip textures whose files already exist in the output directory, instead of failing
+        no_clobber: bool,
+        #[clap(long, conflicts_with_all = ["lossless", "keep_bits"])]
+        /// Only write the meta files, from the texture headers without decoding any pixels
+        meta_only: bool,
     },
-    /// Create a tpg file from a directory of textures
-    Create {
-        /// Path to the directory containing the textures
+    /// Extract a single texture, writing the image to stdout unless an output file is given
+    ExtractOne {
+        /// Path to the tpg file
+        path: PathBuf,
+        #[clap(long, value_parser = parse_hex_id)]
+        /// Hex id of the texture to extract
+        id: u32,
+        #[clap(short, long)]
+        /// File to write the image to instead of stdout
+        output: Option<PathBuf>,
+        #[clap(long, value_enum, default_value_t = ExtractFormat::Png)]
+        /// File format to extract the texture to
+        format: ExtractFormat,
+        #[clap(long, value_enum, default_value_t = CliEndian::Le)]
+        /// Byte order of the tpg file (the iOS version uses little-endian)
+        endian: CliEndian,
+        #[clap(long)]
+        /// Don't flip the texture vertically, for packages storing it top-down
+        no_flip: bool,
+        #[clap(long)]
+        /// Save the decoded image with premultiplied alpha
+        premultiply: bool,
+    },
+    /// Render an overview image of all the textures in a tpg file, labeled with their ids
+    Montage {
+        /// Path to the tpg file
+        path: PathBuf,
+        /// Path to the output PNG file
+        output: PathBuf,
+        #[clap(long, value_name = "PIXELS", default_value_t = 128)]
+        /// Width and height the textures are scaled to fit
+        size: u32,
+        #[clap(long, default_value_t = 0)]
+        /// Number of textures per row, 0 makes the image about square
+        columns: u32,
+        #[clap(long, value_enum, default_value_t = CliEndian::Le)]
+        /// Byte order of the tpg file (the iOS version uses little-endian)
+        endian: CliEndian,
+    },
+    /// List textures in a tpg file without extracting them
+    List {
+        /// Path to the tpg file
+        path: PathBuf,
+        #[clap(long)]
+        /// Print the textures as a JSON array of objects with their id, width, height and format
+        json: bool,
+        #[clap(long, value_enum, default_value_t = SortKey::Id)]
+        /// Order to list the textures in
+        sort: SortKey,
+        #[clap(long)]
+        /// List the textures in the opposite order
+        reverse: bool,
+    },
+    /// Print statistics about a tpg file
+    #[clap(visible_alias = "stats")]
+    Info {
+        /// Path to the tpg file
+        path: PathBuf,
+        #[clap(long)]
+        /// Also print a table of the color usage of each texture
+        histogram: bool,
+        #[clap(long, value_enum, default_value_t = SortKey::Index, requires = "histogram")]
+        /// Order of the textures in the histogram table
+        sort: SortKey,
+        #[clap(long, requires = "histogram")]
+        /// Print the histogram table in the opposite order
+        reverse: bool,
+    },
+    /// Compare the textures of two tpg files
+    Diff {
+        /// Path to the first tpg file
+        a: PathBuf,
+        /// Path to the second tpg file
+        b: PathBuf,
+        #[clap(long)]
+        /// Print the differences as JSON
+        json: bool,
+    },
+    /// Check a tpg file for structural problems
+    Verify {
+        /// Path to the tpg file
+        path: PathBuf,
+        #[clap(long)]
+        /// Also compare the checksum of the file with the one in the .crc32 file written by create
+        checksum: bool,
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        /// The rows of the textures start at multiples of N bytes, see extract --row-align
+        row_align: u32,
+    },
+    /// Replace a single texture of a tpg file, keeping the rest of it as is
+    Replace {
+        /// Path to the tpg file
         path: PathBuf,
+        #[clap(value_parser = parse_hex_id)]
+        /// Hex id of the texture to replace
+        id: u32,
+        /// Path to the new image
+        image: PathBuf,
+        #[clap(long)]
+        /// Dither colors when converting to the 16-bit formats
+        dither: bool,
+        #[clap(long)]
+        /// Store the texture top-down, without flipping it vertically
+        no_flip: bool,
+    },
+    /// Add a texture to the end of a tpg file, keeping the rest of it as is
+    Add {
+        /// Path to the tpg file
+        path: PathBuf,
+        #[clap(value_parser = parse_hex_id)]
+        /// Hex id of the new texture, which must not be in the package yet
+        id: u32,
+        /// Path to the image of the new texture
+        image: PathBuf,
+        #[clap(long)]
+        /// Format to store the texture in, picked based on its colors if not given
+        format: Option<TextureFormat>,
+        #[clap(long)]
+        /// Dither colors when converting to the 16-bit formats
+        dither: bool,
+        #[clap(long)]
+        /// Store the texture top-down, without flipping it vertically
+        no_flip: bool,
+    },
+    /// Remove textures from a tpg file, keeping the rest of it as is
+    Remove {
+        /// Path to the tpg file
+        path: PathBuf,
+        #[clap(long = "id", value_name = "ID", value_parser = parse_hex_id, required = true)]
+        /// Hex id of a texture to remove (can be repeated)
+        ids: Vec<u32>,
+        #[clap(long)]
+        /// Only warn about ids that are not in the package, instead of failing
+        ignore_missing: bool,
+    },
+    /// Re-encode all textures of a tpg file in another format, without going through image files
+    Convert {
+        /// Path to the tpg file
+        input: PathBuf,
+        /// Path to the output tpg file
+        output: PathBuf,
+        #[clap(long)]
+        /// Format to store all the textures in
+        format: TextureFormat,
+        #[clap(long, value_enum, default_value_t = CliEndian::Le)]
+        /// Byte order of the input tpg file, the output is always little-endian
+        endian: CliEndian,
+        #[clap(long)]
+        /// Dither colors when converting to the 16-bit formats
+        dither: bool,
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        /// Start the data of every texture at a multiple of N bytes, as some loaders require
+        align: u32,
+        #[clap(long)]
+        /// Print the bytes taken by the textures of each format, compared to storing them all as R8G8B8A8
+        format_stats: bool,
+    },
+    /// Rewrite a tpg file with the textures sorted by id and no gaps between their data, without decoding them
+    Repack {
+        /// Path to the tpg file
+        input: PathBuf,
+        /// Path to the output tpg file
+        output: PathBuf,
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        /// Start the data of every texture at a multiple of N bytes, as some loaders require
+        align: u32,
+        #[clap(long)]
+        /// Keep the textures in their order instead of sorting them by id
+        keep_order: bool,
+    },
+    /// Create a tpg file from one or more directories of textures
+    Create {
+        #[clap(required = true)]
+        /// Paths to the directories containing the textures, merged into one package
+        paths: Vec<PathBuf>,
         /// Path to the output tpg file
         result: PathBuf,
         #[clap(long)]
         /// Change the used texture format
         force_format: Option<texture::TextureFormat>,
+        #[clap(long, conflicts_with = "force_format")]
+        /// Pick the smallest fitting format for each texture based on its colors
+        auto_format: bool,
+        #[clap(long = "set-format", value_name = "ID=FORMAT", value_parser = parse_format_override)]
+        /// Change the format of the texture with the given hex id (can be repeated)
+        set_formats: Vec<(u32, TextureFormat)>,
+        #[clap(long = "exclude-id", value_name = "ID", value_parser = parse_hex_id)]
+        /// Leave out the texture with the given hex id (can be repeated)
+        exclude_ids: Vec<u32>,
+        #[clap(long)]
+        /// Allow several textures to share the same id
+        allow_duplicate_ids: bool,
+        #[clap(long)]
+        /// Dither colors when converting to the 16-bit formats
+        dither: bool,
+        #[clap(long)]
+        /// JSON file with the meta of all textures, taking precedence over the sidecar files
+        manifest: Option<PathBuf>,
+        #[clap(long)]
+        /// Store all textures top-down, without flipping them vertically
+        no_flip: bool,
+        #[clap(long, default_value_t = 2048)]
+        /// Fail if the width or height of a texture is larger than this
+        max_dimension: u32,
+        #[clap(long)]
+        /// Fail if the width or height of a texture is not a power of two, instead of only warning
+        require_pow2: bool,
+        #[clap(long)]
+        /// JSON file mapping hex texture ids to file names, to get the ids of the named files from
+        name_map: Option<PathBuf>,
+        #[clap(long, value_name = "TEMPLATE", value_parser = parse_name_template)]
+        /// Get the ids of the files not in the name map from their names, following the template used when extracting them
+        name_template: Option<NameTemplate>,
+        #[clap(long)]
+        /// Treat all images as having premultiplied alpha, not only the ones whose meta says so
+        unpremultiply: bool,
+        #[clap(long)]
+        /// Fail instead of warning when the format of a texture loses alpha or merges many of its colors
+        strict: bool,
+        #[clap(long, value_name = "WxH", value_parser = parse_dimensions)]
+        /// Resize all textures to the given dimensions before encoding them
+        resize: Option<(u32, u32)>,
+        #[clap(long, value_enum, default_value_t = ResizeFilter::Triangle)]
+        /// Filter used by --resize
+        resize_filter: ResizeFilter,
+        #[clap(long)]
+        /// Print the layout of the package instead of writing it
+        dry_run: bool,
+        #[clap(long)]
+        /// Print the bytes taken by the textures of each format, compared to storing them all as R8G8B8A8
+        format_stats: bool,
+        #[clap(long)]
+        /// Write a checksum of the package to <RESULT>.crc32, for verify --checksum to detect corrupted copies
+        with_checksum: bool,
+        #[clap(long, value_name = "ID", value_parser = parse_hex_id)]
+        /// Pack all textures into a single atlas texture with the given hex id, writing their positions to <RESULT>.atlas.json
+        atlas: Option<u32>,
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        /// Start the data of every texture at a multiple of N bytes, as some loaders require
+        align: u32,
+        #[clap(long, value_name = "N", default_value_t = 0)]
+        /// Reserve N zero bytes after the package header, for variants of the format with a larger one
+        header_reserve: u32,
+        #[clap(long, value_name = "N", default_value_t = 1)]
+        /// Pad the rows of the textures with zeros to multiples of N bytes, for variants that expect it
+        row_align: u32,
+        #[clap(long)]
+        /// Also load the textures in subdirectories of the directory
+        recursive: bool,
+        #[clap(long, value_enum, value_name = "HASH", conflicts_with = "sequential_ids")]
+        /// Take the ids of the textures not in the name map from a hash of their file names, ignoring the ids in their meta
+        id_from_name: Option<NameHash>,
+        #[clap(long)]
+        /// Number the textures from 0 in the order of their file names, ignoring the ids in their meta
+        sequential_ids: bool,
+        #[clap(long, requires = "sequential_ids", conflicts_with = "manifest")]
+        /// Also write the numbers given by --sequential-ids to the meta files
+        write_ids: bool,
+        #[clap(long, value_enum)]
+        /// Store the color channels of all textures in this order, instead of the one in their meta
+        channel_order: Option<ChannelOrder>,
+        #[clap(long, value_enum)]
+        /// Generate the mip levels of all textures in this color space, instead of the one in their meta
+        mip_gamma: Option<MipGamma>,
     },
 }
 
-fn main() {
+#[derive(clap::ValueEnum, Copy, Clone, Debug)]
+enum ExtractFormat {
+    /// Decoded RGBA8 PNG images
+    Png,
+    /// Decoded RGBA8 TGA images
+    Tga,
+    /// Decoded RGBA8 BMP images
+    Bmp,
+    /// Decoded RGBA8 lossless WebP images, only available when built with the webp feature
+    Webp,
+    /// DDS files keeping the original pixel format of the texture
+    Dds,
+    /// KTX2 files keeping the original pixel format of the texture
+    Ktx2,
+    /// A single blob of decoded RGBA8 pixels of all textures, with a table of contents
+    Raw,
+}
+
+impl ExtractFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExtractFormat::Png => "png",
+            ExtractFormat::Tga => "tga",
+            ExtractFormat::Bmp => "bmp",
+            ExtractFormat::Webp => "webp",
+            ExtractFormat::Dds => "dds",
+            ExtractFormat::Ktx2 => "ktx2",
+            ExtractFormat::Raw => "raw",
+        }
+    }
+
+    /// Encoder used for the formats that store the decoded image
+    fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            ExtractFormat::Png => Some(ImageFormat::Png),
+            ExtractFormat::Tga => Some(ImageFormat::Tga),
+            ExtractFormat::Bmp => Some(ImageFormat::Bmp),
+            ExtractFormat::Webp => Some(ImageFormat::WebP),
+            ExtractFormat::Dds | ExtractFormat::Ktx2 | ExtractFormat::Raw => None,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug)]
+enum ResizeFilter {
+    /// Nearest neighbor, keeps pixel art sharp
+    Nearest,
+    /// Linear interpolation
+    Triangle,
+    /// Lanczos with a window of 3, the sharpest but slowest
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug)]
+enum CliEndian {
+    Le,
+    Be,
+}
+
+impl From<CliEndian> for Endian {
+    fn from(endian: CliEndian) -> Self {
+        match endian {
+            CliEndian::Le => Endian::Little,
+            CliEndian::Be => Endian::Big,
+        }
+    }
+}
+
+/// Order in which `list` and `info` print the textures
+#[derive(clap::ValueEnum, Copy, Clone, Debug)]
+enum SortKey {
+    Id,
+    /// Position in the package
+    Index,
+    /// Data size, smallest first
+    Size,
+    /// Format, in the order of the format values
+    Format,
+}
+
+/// Sorts the textures of a package along with their indices, ties being broken by id
+fn sort_entries(entries: &[TextureEntry], key: SortKey, reverse: bool) -> Result<Vec<(usize, &TextureEntry)>> {
+    let mut keyed = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let header = &entry.header;
+        let primary = match key {
+            SortKey::Id => 0,
+            SortKey::Index => index as u64,
+            SortKey::Size => header.data_size()? as u64,
+            SortKey::Format => header.texture_format as u64,
+        };
+        keyed.push(((primary, header.id, index), (index, entry)));
+    }
+    keyed.sort_by_key(|&(key, _)| key);
+    if reverse {
+        keyed.reverse();
+    }
+    Ok(keyed.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// Parses a texture id written in hex, with an optional `0x` prefix
+fn parse_hex_id(s: &str) -> Result<u32> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u32::from_str_radix(digits, 16).with_context(|| format!("invalid hex texture id {:?}", s))
+}
+
+/// Progress bar counting processed textures, only shown when built with the progress feature
+///
+/// indicatif hides it by itself when stderr is not a terminal.
+#[derive(Clone)]
+struct Progress {
+    #[cfg(feature = "progress")]
+    bar: indicatif::ProgressBar,
+}
+
+impl Progress {
+    fn new(_len: usize) -> Self {
+        Self {
+            #[cfg(feature = "progress")]
+            bar: if QUIET.load(Ordering::Relaxed) {
+                indicatif::ProgressBar::hidden()
+            } else {
+                indicatif::ProgressBar::new(_len as u64).with_style(
+                    indicatif::ProgressStyle::with_template("{bar:40} {pos}/{len} textures, {eta} left")
+                        .expect("the template is valid"),
+                )
+            },
+        }
+    }
+
+    fn inc(&self) {
+        #[cfg(feature = "progress")]
+        self.bar.inc(1);
+    }
+
+    fn finish(&self) {
+        #[cfg(feature = "progress")]
+        self.bar.finish_and_clear();
+    }
+}
+
+/// A texture as printed by `list --json`
+#[derive(serde::Serialize)]
+struct ListedTexture {
+    id: u32,
+    /// Position of the texture in the package
+    index: usize,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+/// Prints the bytes the textures of each format take, compared to storing all of them as R8G8B8A8
+fn print_format_stats(textures: &[Texture]) -> Result<()> {
+    let size_as = |tex: &Texture, format| -> Result<u64> {
+        let mut size = 0;
+        for level in std::iter::once(&tex.data).chain(tex.mips.iter()) {
+            size += texture::data_size(format, level.width(), level.height())? as u64;
+        }
+        Ok(size)
+    };
+    let change = |bytes: u64, rgba: u64| match rgba {
+        0 => "-".to_string(),
+        rgba => format!("{:+.1}%", (bytes as f64 / rgba as f64 - 1.0) * 100.0),
+    };
+
+    println!("{:<8} {:>8} {:>10} {:>11} {:>8}", "format", "textures", "bytes", "as R8G8B8A8", "change");
+    let (mut total_bytes, mut total_rgba) = (0, 0);
+    for &format in TextureFormat::value_variants() {
+        let textures: Vec<_> = textures.iter().filter(|t| t.meta.texture_format == format).collect();
+        if textures.is_empty() {
+            continue;
+        }
+        let (mut bytes, mut rgba) = (0, 0);
+        for tex in textures.iter() {
+            bytes += size_as(tex, format)?;
+            rgba += size_as(tex, TextureFormat::R8G8B8A8)?;
+        }
+        println!(
+            "{:<8} {:>8} {:>10} {:>11} {:>8}",
+            format!("{:?}", format), textures.len(), bytes, rgba, change(bytes, rgba)
+        );
+        total_bytes += bytes;
+        total_rgba += rgba;
+    }
+    println!(
+        "{:<8} {:>8} {:>10} {:>11} {:>8}",
+        "total", textures.len(), total_bytes, total_rgba, change(total_bytes, total_rgba)
+    );
+    Ok(())
+}
+
+/// Path of the file storing the checksum of a package, next to it
+fn checksum_path(package: &Path) -> PathBuf {
+    let mut path = package.as_os_str().to_owned();
+    path.push(".crc32");
+    PathBuf::from(path)
+}
+
+/// What extract does with files that are already in the output directory
+#[derive(Copy, Clone, Debug)]
+enum ExistingFiles {
+    Fail,
+    Skip,
+    Overwrite,
+}
+
+/// Checks whether the files of a texture can be written, failing or warning about existing ones
+fn check_existing(paths: &[&Path], what: &str, existing: ExistingFiles) -> Result<bool> {
+    let path = match paths.iter().find(|p| p.exists()) {
+        Some(path) => path,
+        None => return Ok(true),
+    };
+    match existing {
+        ExistingFiles::Overwrite => Ok(true),
+        ExistingFiles::Skip => {
+            warning!("skipping {}, {} already exists", what, path.display());
+            Ok(false)
+        }
+        ExistingFiles::Fail => bail!(
+            "{} already exists, pass --overwrite to replace it or --no-clobber to skip {}",
+            path.display(),
+            what
+        ),
+    }
+}
+
+/// Settings of the extract command shared by all textures
+#[derive(Copy, Clone, Debug)]
+struct ExtractOptions<'a> {
+    format: ExtractFormat,
+    endian: Endian,
+    lossless: bool,
+    no_flip: bool,
+    row_align: u32,
+    premultiply: bool,
+    pretty_meta: bool,
+    meta_format: MetaFormat,
+    keep_bits: bool,
+    channel_order: ChannelOrder,
+    existing: ExistingFiles,
+    names: &'a NameMap,
+    name_template: &'a NameTemplate,
+}
+
+fn read_name_map(path: Option<&Path>) -> Result<NameMap> {
+    match path {
+        Some(path) => smb_tex::names::read_name_map(path)
+            .with_context(|| format!("failed to read name map {}", path.display())),
+        None => Ok(NameMap::default()),
+    }
+}
+
+/// Decodes a single texture of the package, along with its meta
+fn decode_entry(package: &[u8], index: usize, entry: &TextureEntry, options: &ExtractOptions) -> Result<(TextureMeta, RgbaImage)> {
+    let ExtractOptions { endian, no_flip, row_align, premultiply, channel_order, .. } = *options;
+    let _span = tracing::debug_span!("texture", id = %format_args!("{:08x}", entry.header.id)).entered();
+    let meta = extracted_meta(index, entry, options);
+    let raw = entry.raw_data_with_row_align(package, row_align)
+        .with_context(|| format!("failed to read data of texture {:08x}", meta.id))?;
+    let args = TextureDataArgs {
+        no_flip,
+        channel_order,
+        ..entry.header.data_args_with_row_align(row_align)?
+    };
+    let mut image = texture::decode_texture_with_endian(raw, &args, endian)
+        .with_context(|| format!("failed to decode texture {:08x}", meta.id))?;
+    if premultiply {
+        texture::premultiply_alpha(&mut image);
+    }
+    Ok((meta, image))
+}
+
+/// Parses dimensions written as `<width>x<height>`
+fn parse_dimensions(s: &str) -> Result<(u32, u32)> {
+    let (width, height) = s.split_once('x')
+        .with_context(|| format!("expected WxH, got {:?}", s))?;
+    let width = width.parse().with_context(|| format!("invalid width {:?}", width))?;
+    let height = height.parse().with_context(|| format!("invalid height {:?}", height))?;
+    Ok((width, height))
+}
+
+fn parse_name_template(s: &str) -> Result<NameTemplate> {
+    Ok(NameTemplate::new(s)?)
+}
+
+/// Parses a `<hex id>=<format>` texture format override
+fn parse_format_override(s: &str) -> Result<(u32, TextureFormat)> {
+    let (id, format) = s.split_once('=')
+        .with_context(|| format!("expected ID=FORMAT, got {:?}", s))?;
+    let format = TextureFormat::from_str(format, true).map_err(|_| {
+        let names: Vec<_> = TextureFormat::value_variants().iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        anyhow::anyhow!("invalid texture format {:?}, expected one of {}", format, names.join(", "))
+    })?;
+    Ok((parse_hex_id(id)?, format))
+}
+
+/// Encodes a decoded texture as an image file
+fn encode_image(image: &RgbaImage, image_format: ImageFormat) -> Result<Vec<u8>> {
+    match image_format {
+        ImageFormat::Png => Ok(texture::encode_png(image)?),
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => Ok(texture::encode_webp(image)?),
+        _ => {
+            let mut buf = Cursor::new(Vec::new());
+            image.write_to(&mut buf, image_format)?;
+            Ok(buf.into_inner())
+        }
+    }
+}
+
+/// Meta of a texture as written by extract, before the image hash of `--lossless` is added
+fn extracted_meta(index: usize, entry: &TextureEntry, options: &ExtractOptions) -> TextureMeta {
+    TextureMeta {
+        order: Some(index as u32),
+        no_flip: options.no_flip,
+        premultiplied: options.premultiply,
+        channel_order: options.channel_order,
+        ..entry.header.meta()
+    }
+}
+
+/// File name of an extracted texture, without the extension
+fn extracted_name(index: usize, entry: &TextureEntry, options: &ExtractOptions) -> String {
+    match options.names.get(entry.header.id) {
+        Some(name) => name.to_string(),
+        None => options.name_template.name(entry.header.id, index as u32, entry.header.texture_format),
+    }
+}
+
+/// Writes only the meta file of a texture into the output directory, for `--meta-only`
+fn extract_meta(index: usize, entry: &TextureEntry, result: &Path, options: &ExtractOptions) -> Result<()> {
+    let meta = extracted_meta(index, entry, options);
+    let meta_path = result.join(format!("{}.{}", extracted_name(index, entry, options), options.meta_format.extension()));
+    if !check_existing(&[&meta_path], &format!("texture {:08x}", meta.id), options.existing)? {
+        return Ok(());
+    }
+    std::fs::write(&meta_path, smb_tex::meta::write_meta(&meta, options.pretty_meta, options.meta_format))
+        .with_context(|| format!("failed to write texture meta to {}", meta_path.display()))
+}
+
+/// Writes a single texture of the package and its meta file into the output directory
+fn extract_texture(
+    package: &[u8],
+    index: usize,
+    entry: &TextureEntry,
+    result: &Path,
+    options: &ExtractOptions,
+) -> Result<()> {
+    let ExtractOptions {
+        format,
+        endian,
+        lossless,
+        no_flip,
+        row_align,
+        premultiply,
+        pretty_meta,
+        meta_format,
+        keep_bits,
+        channel_order,
+        existing,
+        ..
+    } = *options;
+    let _span = tracing::debug_span!("texture", id = %format_args!("{:08x}", entry.header.id)).entered();
+    tracing::debug!(offset = entry.data_offset, format = ?entry.header.texture_format, width = entry.header.width, height = entry.header.height, "extracting texture");
+    let mut meta = extracted_meta(index, entry, options);
+    let raw = entry.raw_data_with_row_align(package, row_align)
+        .with_context(|| format!("failed to read data of texture {:08x}", meta.id))?;
+    let name = extracted_name(index, entry, options);
+    let path = result.join(format!("{}.{}", name, format.extension()));
+    let raw_path = result.join(format!("{}.bin", name));
+    let meta_path = result.join(format!("{}.{}", name, meta_format.extension()));
+    let mut paths = vec![path.as_path(), meta_path.as_path()];
+    if lossless {
+        paths.push(&raw_path);
+    }
+    // checked before anything is written, so that a texture's files are never only partly replaced
+    if !check_existing(&paths, &format!("texture {:08x}", meta.id), existing)? {
+        return Ok(());
+    }
+    let saved: Result<()> = match format.image_format() {
+        Some(image_format) => {
+            let args = TextureDataArgs {
+                no_flip,
+                channel_order,
+                ..entry.header.data_args_with_row_align(row_align)?
+            };
+            let mut image = texture::decode_texture_with_endian(raw, &args, endian)
+                .with_context(|| format!("failed to decode texture {:08x}", meta.id))?;
+            if premultiply {
+                texture::premultiply_alpha(&mut image);
+            }
+            if lossless {
+                meta.image_hash = Some(texture::image_hash(&image));
+                std::fs::write(&raw_path, texture::to_little_endian(raw, meta.texture_format, endian))
+                    .with_context(|| format!("failed to write raw texture data to {}", raw_path.display()))?;
+            }
+            let encoded = if keep_bits && meta.texture_format.is_16_bit() {
+                texture::encode_png_16(&image, meta.texture_format).map_err(Into::into)
+            } else {
+                encode_image(&image, image_format)
+            };
+            encoded.and_then(|data| std::fs::write(&path, data).map_err(Into::into))
+        }
+        None => {
+            let container = match format {
+                ExtractFormat::Ktx2 => smb_tex::ktx2::write_ktx2(&entry.header, raw, endian, no_flip)?,
+                ExtractFormat::Dds => smb_tex::dds::write_dds(&entry.header, raw, endian, no_flip)?,
+                _ => unreachable!("raw dumps are written for the whole package at once"),
+            };
+            std::fs::write(&path, container).map_err(Into::into)
+        }
+    };
+    saved.with_context(|| format!("failed to save texture to {}", path.display()))?;
+    std::fs::write(&meta_path, smb_tex::meta::write_meta(&meta, pretty_meta, meta_format))
+        .with_context(|| format!("failed to write texture meta to {}", meta_path.display()))?;
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
     let cli = Cli::parse();
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    // without a subscriber the library's events cost next to nothing
+    if cli.verbose > 0 {
+        let level = if cli.verbose == 1 { tracing::Level::DEBUG } else { tracing::Level::TRACE };
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(std::io::stderr)
+            .init();
+    }
 
-    match cli.command {
-        CliCommand::Extract { path, result } => {
-            let data = std::fs::read(path).unwrap();
-            let tp = texture::read_texture_package(&data).unwrap();
+    #[cfg(feature = "rayon")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(cli.threads)
+            .build()
+            .context("failed to start the thread pool")?;
+        pool.install(|| run(cli.command))
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        if cli.threads > 1 {
+            warning!("smb_tex was built without rayon support, so --threads has no effect");
+        }
+        run(cli.command)
+    }
+}
 
-            std::fs::create_dir_all(&result).unwrap();
+fn run(command: CliCommand) -> Result<()> {
+    match command {
+        CliCommand::Extract {
+            path,
+            result,
+            ids,
+            format,
+            endian,
+            lossless,
+            no_flip,
+            row_align,
+            name_map,
+            name_template,
+            premultiply,
+            pretty_meta,
+            meta_format,
+            keep_bits,
+            channel_order,
+            overwrite,
+            no_clobber,
+            meta_only,
+        } => {
+            if meta_only && matches!(format, ExtractFormat::Raw) {
+                bail!("--meta-only can't be used with --format raw, whose meta is in the table of contents");
+            }
+            if lossless && format.image_format().is_none() {
+                bail!("--lossless can't be used with --format {}", format.extension());
+            }
+            if cfg!(not(feature = "webp")) && matches!(format, ExtractFormat::Webp) {
+                bail!("smb_tex was built without WebP support, rebuild it with --features webp");
+            }
+            if premultiply && matches!(format, ExtractFormat::Dds | ExtractFormat::Ktx2) {
+                bail!("--premultiply can't be used with --format {}", format.extension());
+            }
+            if keep_bits && !matches!(format, ExtractFormat::Png) {
+                bail!("--keep-bits can only be used with --format png");
+            }
+            if !channel_order.is_rgba() && matches!(format, ExtractFormat::Dds | ExtractFormat::Ktx2) {
+                bail!("--channel-order can't be used with --format {}", format.extension());
+            }
+            if keep_bits && premultiply {
+                // premultiplied colors are no longer packed values
+                bail!("--keep-bits can't be used with --premultiply");
+            }
+            if row_align > 1 && matches!(format, ExtractFormat::Dds | ExtractFormat::Ktx2) {
+                bail!("--row-align can't be used with --format {}", format.extension());
+            }
+            if row_align > 1 && lossless {
+                // create packs the raw data tightly unless told otherwise, so the padding would be lost anyway
+                bail!("--row-align can't be used with --lossless");
+            }
+            let endian = Endian::from(endian);
+            let names = read_name_map(name_map.as_deref())?;
+            let options = ExtractOptions {
+                format,
+                endian,
+                lossless,
+                no_flip,
+                row_align,
+                premultiply,
+                pretty_meta,
+                meta_format,
+                keep_bits,
+                channel_order,
+                existing: if overwrite {
+                    ExistingFiles::Overwrite
+                } else if no_clobber {
+                    ExistingFiles::Skip
+                } else {
+                    ExistingFiles::Fail
+                },
+                names: &names,
+                name_template: &name_template,
+            };
+            // the pixel data isn't needed for the meta files alone, so then only the headers are read
+            let (data, headers) = if meta_only {
+                let file = std::fs::File::open(&path)
+                    .with_context(|| format!("failed to open tpg file at {}", path.display()))?;
+                let headers = texture::read_texture_headers_from_with_endian(std::io::BufReader::new(file), endian)
+                    .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+                (Vec::new(), headers)
+            } else {
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+                let headers = texture::read_texture_headers_with_endian(&data, endian)
+                    .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+                (data, headers)
+            };
+
+            std::fs::create_dir_all(&result)
+                .with_context(|| format!("failed to create output directory {}", result.display()))?;
+
+            let selected: Vec<_> = headers.entries.iter()
+                .enumerate()
+                .filter(|(_, e)| ids.is_empty() || ids.contains(&e.header.id))
+                .collect();
+            let progress = Progress::new(selected.len());
+            if meta_only {
+                for &(index, entry) in selected.iter() {
+                    extract_meta(index, entry, &result, &options)?;
+                    progress.inc();
+                }
+            } else if let ExtractFormat::Raw = format {
+                let toc_path = result.join(smb_tex::raw::TOC_FILE_NAME);
+                let data_path = result.join(smb_tex::raw::DATA_FILE_NAME);
+                if check_existing(&[&toc_path, &data_path], "the raw dump", options.existing)? {
+                    let decode = |&(index, entry): &(usize, &TextureEntry)| {
+                        let decoded = decode_entry(&data, index, entry, &options);
+                        progress.inc();
+                        decoded
+                    };
+                    #[cfg(feature = "rayon")]
+                    let textures = selected.par_iter().map(decode).collect::<Result<Vec<_>>>()?;
+                    #[cfg(not(feature = "rayon"))]
+                    let textures = selected.iter().map(decode).collect::<Result<Vec<_>>>()?;
+                    smb_tex::raw::write_raw_dump(&result, &textures)
+                        .with_context(|| format!("failed to write raw dump to {}", result.display()))?;
+                }
+            } else {
+                let extract = |&(index, entry): &(usize, &TextureEntry)| -> Result<()> {
+                    extract_texture(&data, index, entry, &result, &options)?;
+                    progress.inc();
+                    Ok(())
+                };
+                #[cfg(feature = "rayon")]
+                selected.par_iter().try_for_each(extract)?;
+                #[cfg(not(feature = "rayon"))]
+                selected.iter().try_for_each(extract)?;
+            }
+            progress.finish();
+
+            if !meta_only {
+                let counts = headers.stored_level_counts(data.len() as u64)?;
+                for &(index, entry) in selected.iter() {
+                    if counts[index] > 1 {
+                        warning!(
+                            "texture {:08x} seems to have {} levels stored, but only the base one is extracted; \
+                             set \"mip_count\": {} in its meta for create to generate the mip levels again",
+                            entry.header.id, counts[index], counts[index]
+                        );
+                    }
+                }
+            }
+
+            let missing: Vec<_> = ids.iter()
+                .filter(|&&id| !headers.entries.iter().any(|e| e.header.id == id))
+                .map(|id| format!("{:08x}", id))
+                .collect();
+            if !missing.is_empty() {
+                warning!("textures not found in the package: {}", missing.join(", "));
+            }
+        }
+        CliCommand::ExtractOne { path, id, output, format, endian, no_flip, premultiply } => {
+            if matches!(format, ExtractFormat::Raw) {
+                bail!("--format raw can only be used with extract");
+            }
+            if cfg!(not(feature = "webp")) && matches!(format, ExtractFormat::Webp) {
+                bail!("smb_tex was built without WebP support, rebuild it with --features webp");
+            }
+            if premultiply && format.image_format().is_none() {
+                bail!("--premultiply can't be used with --format {}", format.extension());
+            }
+            let endian = Endian::from(endian);
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+            let headers = texture::read_texture_headers_with_endian(&data, endian)
+                .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+            let (index, entry) = headers.entries.iter()
+                .enumerate()
+                .find(|(_, e)| e.header.id == id)
+                .with_context(|| format!("no texture with id {:08x} in {}", id, path.display()))?;
 
-            for tex in tp.textures.iter() {
-                let meta = &tex.meta;
-                let path = result.join(format!("{:08x}.png", meta.id));
-                tex.data.save(path).unwrap();
-                std::fs::write(
-                    result.join(format!("{:08x}.json", meta.id)),
-                    serde_json::to_string_pretty(&meta).unwrap()
-                ).unwrap()
+            let encoded = match format.image_format() {
+                Some(image_format) => {
+                    let names = NameMap::default();
+                    let options = ExtractOptions {
+                        format,
+                        endian,
+                        lossless: false,
+                        no_flip,
+                        row_align: 1,
+                        premultiply,
+                        pretty_meta: false,
+                        meta_format: MetaFormat::Json,
+                        keep_bits: false,
+                        channel_order: ChannelOrder::Rgba,
+                        existing: ExistingFiles::Overwrite,
+                        names: &names,
+                        name_template: &NameTemplate::default(),
+                    };
+                    let (_, image) = decode_entry(&data, index, entry, &options)?;
+                    encode_image(&image, image_format)
+                        .with_context(|| format!("failed to encode texture {:08x}", id))?
+                }
+                None => {
+                    let raw = entry.raw_data(&data)
+                        .with_context(|| format!("failed to read data of texture {:08x}", id))?;
+                    match format {
+                        ExtractFormat::Ktx2 => smb_tex::ktx2::write_ktx2(&entry.header, raw, endian, no_flip)?,
+                        ExtractFormat::Dds => smb_tex::dds::write_dds(&entry.header, raw, endian, no_flip)?,
+                        _ => unreachable!("raw dumps are rejected above"),
+                    }
+                }
+            };
+            match output {
+                Some(output) => std::fs::write(&output, encoded)
+                    .with_context(|| format!("failed to write texture to {}", output.display()))?,
+                None => std::io::stdout().lock().write_all(&encoded)
+                    .context("failed to write texture to stdout")?,
             }
         }
-        CliCommand::Create { path, result, force_format } => {
-            let mut tp = TexturePackage::from_directory(&path).unwrap();
+        CliCommand::List { path, json, sort, reverse } => {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("failed to open tpg file at {}", path.display()))?;
+            let headers = texture::read_texture_headers_from(std::io::BufReader::new(file))
+                .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+
+            let entries = sort_entries(&headers.entries, sort, reverse)?;
+
+            if json {
+                let textures: Vec<_> = entries.iter()
+                    .map(|&(index, e)| ListedTexture {
+                        id: e.header.id,
+                        index,
+                        width: e.header.width,
+                        height: e.header.height,
+                        format: e.header.texture_format,
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&textures)?);
+            } else {
+                for &(index, entry) in entries.iter() {
+                    let header = &entry.header;
+                    println!(
+                        "{:08x} {}x{} {:?} {} {}",
+                        header.id, header.width, header.height, header.texture_format, header.data_size()?, index
+                    );
+                }
+            }
+        }
+        CliCommand::Info { path, histogram, sort, reverse } => {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("failed to open tpg file at {}", path.display()))?;
+            let mut reader = std::io::BufReader::new(file);
+            let headers = texture::read_texture_headers_from(&mut reader)
+                .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+            let entries = &headers.entries;
+
+            let mut pixels = 0u64;
+            let mut data_size = 0u64;
+            for entry in entries.iter() {
+                pixels += entry.header.width as u64 * entry.header.height as u64;
+                data_size += entry.header.data_size()? as u64;
+            }
+
+            println!("textures: {}", entries.len());
+            println!("pixels: {}", pixels);
+            println!("data size: {} bytes", data_size);
+            println!("formats:");
+            for &format in TextureFormat::value_variants() {
+                let count = entries.iter().filter(|e| e.header.texture_format == format).count();
+                println!("  {:?}: {}", format, count);
+            }
+            let area = |e: &&TextureEntry| e.header.width as u64 * e.header.height as u64;
+            if let (Some(smallest), Some(largest)) = (entries.iter().min_by_key(area), entries.iter().max_by_key(area)) {
+                println!("smallest: {}x{}", smallest.header.width, smallest.header.height);
+                println!("largest: {}x{}", largest.header.width, largest.header.height);
+            }
+
+            if histogram {
+                println!();
+                println!("{:>5} {:<8} {:>9} {:<8} {:<5} {:>6}", "index", "id", "size", "format", "alpha", "colors");
+                for (index, entry) in sort_entries(entries, sort, reverse)? {
+                    let header = &entry.header;
+                    let texture = texture::load_texture(&mut reader, entry)
+                        .with_context(|| format!("failed to decode texture {:08x}", header.id))?;
+                    let stats = smb_tex::analysis::color_stats(&texture.data);
+                    println!(
+                        "{:>5} {:08x} {:>9} {:<8} {:<5} {:>6}",
+                        index,
+                        header.id,
+                        format!("{}x{}", header.width, header.height),
+                        format!("{:?}", header.texture_format),
+                        if stats.translucent { "yes" } else { "no" },
+                        stats.distinct_colors,
+                    );
+                }
+            }
+        }
+        CliCommand::Diff { a, b, json } => {
+            let a_data = std::fs::read(&a)
+                .with_context(|| format!("failed to read tpg file at {}", a.display()))?;
+            let b_data = std::fs::read(&b)
+                .with_context(|| format!("failed to read tpg file at {}", b.display()))?;
+            let differences = smb_tex::diff::diff_packages(&a_data, &b_data)
+                .with_context(|| format!("failed to compare {} and {}", a.display(), b.display()))?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&differences)?);
+            } else {
+                for difference in differences.iter() {
+                    println!("{}", difference);
+                }
+            }
+        }
+        CliCommand::Verify { path, checksum, row_align } => {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("failed to open tpg file at {}", path.display()))?;
+            let file_size = file.metadata()
+                .with_context(|| format!("failed to read tpg file at {}", path.display()))?
+                .len();
+            let headers = texture::read_texture_headers_from(std::io::BufReader::new(file))
+                .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+            let mut problems = smb_tex::verify::verify_headers_with_row_align(&headers, file_size, row_align);
+            if checksum {
+                let checksum_path = checksum_path(&path);
+                let recorded = std::fs::read_to_string(&checksum_path)
+                    .with_context(|| format!("failed to read checksum file {}", checksum_path.display()))?;
+                let expected = u32::from_str_radix(recorded.trim(), 16)
+                    .with_context(|| format!("invalid checksum in {}", checksum_path.display()))?;
+                let file = std::fs::File::open(&path)
+                    .with_context(|| format!("failed to open tpg file at {}", path.display()))?;
+                let actual = smb_tex::verify::checksum(file)
+                    .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+                if actual != expected {
+                    problems.push(smb_tex::verify::Problem::ChecksumMismatch { expected, actual });
+                }
+            }
+
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                bail!("found {} problem(s) in {}", problems.len(), path.display());
+            }
+            println!("no problems found");
+        }
+        CliCommand::Replace { path, id, image, dither, no_flip } => {
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+            let new_image = image::open(&image)
+                .with_context(|| format!("failed to read image {}", image.display()))?
+                .into_rgba8();
+
+            let options = EncodeOptions {
+                dither,
+                no_flip,
+                ..EncodeOptions::default()
+            };
+            let data = texture::replace_texture(&data, id, &new_image, &options)
+                .with_context(|| format!("failed to replace texture {:08x} in {}", id, path.display()))?;
+            std::fs::write(&path, data)
+                .with_context(|| format!("failed to write tpg file to {}", path.display()))?;
+        }
+        CliCommand::Add { path, id, image, format, dither, no_flip } => {
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+            let new_image = image::open(&image)
+                .with_context(|| format!("failed to read image {}", image.display()))?
+                .into_rgba8();
+
+            let texture_format = format.unwrap_or_else(|| smb_tex::analysis::choose_format(&new_image));
+            let texture = Texture {
+                meta: TextureMeta {
+                    id,
+                    texture_format,
+                    no_flip,
+                    ..TextureMeta::default()
+                },
+                data: new_image,
+                mips: Vec::new(),
+                raw: None,
+            };
+            let options = EncodeOptions {
+                dither,
+                no_flip,
+                ..EncodeOptions::default()
+            };
+            let data = texture::add_texture(&data, &texture, &options)
+                .with_context(|| format!("failed to add texture {:08x} to {}", id, path.display()))?;
+            std::fs::write(&path, data)
+                .with_context(|| format!("failed to write tpg file to {}", path.display()))?;
+        }
+        CliCommand::Remove { path, ids, ignore_missing } => {
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+
+            let mut ids = ids;
+            if ignore_missing {
+                let headers = texture::read_texture_headers(&data)
+                    .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+                let (present, missing): (Vec<_>, Vec<_>) = ids.iter()
+                    .partition(|&&id| headers.entries.iter().any(|e| e.header.id == id));
+                if !missing.is_empty() {
+                    let missing: Vec<_> = missing.iter().map(|id| format!("{:08x}", id)).collect();
+                    warning!("textures not found in the package: {}", missing.join(", "));
+                }
+                ids = present;
+            }
+            let data = texture::remove_textures(&data, &ids)
+                .with_context(|| format!("failed to remove textures from {}", path.display()))?;
+            std::fs::write(&path, data)
+                .with_context(|| format!("failed to write tpg file to {}", path.display()))?;
+        }
+        CliCommand::Montage { path, output, size, columns, endian } => {
+            let data = std::fs::read(&path)
+                .with_context(|| format!("failed to read tpg file at {}", path.display()))?;
+            let tp = texture::read_texture_package_with_endian(&data, Endian::from(endian))
+                .with_context(|| format!("failed to parse tpg file at {}", path.display()))?;
+            let montage = smb_tex::montage::montage(&tp.textures, size, columns);
+            std::fs::write(&output, texture::encode_png(&montage)?)
+                .with_context(|| format!("failed to write montage to {}", output.display()))?;
+        }
+        CliCommand::Convert { input, output, format, endian, dither, align, format_stats } => {
+            let data = std::fs::read(&input)
+                .with_context(|| format!("failed to read tpg file at {}", input.display()))?;
+            let mut tp = texture::read_texture_package_with_endian(&data, Endian::from(endian))
+                .with_context(|| format!("failed to parse tpg file at {}", input.display()))?;
+            for tex in tp.textures.iter_mut() {
+                tex.meta.texture_format = format;
+            }
+
+            let mut write_options = WriteOptions::default();
+            write_options.encode.dither = dither;
+            write_options.align = align;
+            let data = texture::write_texture_package_with_options(&tp, &write_options)?;
+            std::fs::write(&output, &data)
+                .with_context(|| format!("failed to write tpg file to {}", output.display()))?;
+            if format_stats {
+                print_format_stats(&tp.textures)?;
+            }
+        }
+        CliCommand::Repack { input, output, align, keep_order } => {
+            let data = std::fs::read(&input)
+                .with_context(|| format!("failed to read tpg file at {}", input.display()))?;
+            let options = texture::RepackOptions { align, keep_order };
+            let data = texture::repack_package(&data, &options)
+                .with_context(|| format!("failed to repack {}", input.display()))?;
+            std::fs::write(&output, &data)
+                .with_context(|| format!("failed to write tpg file to {}", output.display()))?;
+        }
+        CliCommand::Create {
+            paths,
+            result,
+            force_format,
+            auto_format,
+            set_formats,
+            exclude_ids,
+            allow_duplicate_ids,
+            dither,
+            manifest,
+            no_flip,
+            max_dimension,
+            require_pow2,
+            name_map,
+            name_template,
+            unpremultiply,
+            strict,
+            resize,
+            resize_filter,
+            dry_run,
+            format_stats,
+            with_checksum,
+            atlas,
+            align,
+            header_reserve,
+            row_align,
+            recursive,
+            id_from_name,
+            sequential_ids,
+            write_ids,
+            channel_order,
+            mip_gamma,
+        } => {
+            let options = DirectoryOptions {
+                allow_duplicate_ids,
+                max_dimension: Some(max_dimension),
+                require_pow2,
+                names: read_name_map(name_map.as_deref())?,
+                name_template,
+                resize: resize.map(|(width, height)| Resize {
+                    width,
+                    height,
+                    filter: resize_filter.into(),
+                }),
+                recursive,
+                id_from_name: id_from_name.map(NameHash::function),
+                sequential_ids,
+                force_format,
+            };
+            let is_raw_dump = |path: &PathBuf| path.join(smb_tex::raw::TOC_FILE_NAME).exists();
+            let tp = match (paths.as_slice(), manifest) {
+                ([_], None) if sequential_ids && is_raw_dump(&paths[0]) => {
+                    bail!("--sequential-ids can't be used with raw dumps, whose textures have no files of their own")
+                }
+                ([path], None) if is_raw_dump(path) => smb_tex::raw::read_raw_dump(path, &options),
+                ([path], Some(manifest)) => {
+                    let manifest = texture::read_manifest(&manifest)
+                        .with_context(|| format!("failed to read manifest {}", manifest.display()))?;
+                    TexturePackage::from_directory_with_manifest(path, &manifest, &options)
+                }
+                (_, Some(_)) => bail!("--manifest can only be used with a single directory"),
+                (paths, None) => {
+                    if let Some(path) = paths.iter().find(|p| is_raw_dump(p)) {
+                        bail!("{} is a raw dump, which can only be packed on its own", path.display());
+                    }
+                    TexturePackage::from_directories_with_options(paths, &options)
+                }
+            };
+            let dirs: Vec<_> = paths.iter().map(|p| p.display().to_string()).collect();
+            let mut tp = tp.with_context(|| format!("failed to load textures from {}", dirs.join(", ")))?;
+            for &id in exclude_ids.iter() {
+                if !tp.textures.iter().any(|t| t.meta.id == id) {
+                    warning!("no texture {:08x} to exclude", id);
+                }
+            }
+            tp.textures.retain(|t| !exclude_ids.contains(&t.meta.id));
+            for tex in tp.textures.iter_mut().filter(|t| unpremultiply || t.meta.premultiplied) {
+                tex.unpremultiply();
+            }
+            let mut atlas_entries = None;
+            if let Some(id) = atlas {
+                let (atlas, entries) = smb_tex::atlas::atlas_package(&tp, id);
+                for tex in atlas.textures.iter() {
+                    tex.check_dimensions(&options)
+                        .context("failed to pack the textures into an atlas")?;
+                }
+                atlas_entries = Some(entries);
+                tp = atlas;
+            }
+            for tex in tp.textures.iter().filter(|t| !t.is_pow2()) {
+                warning!(
+                    "texture {:08x} is {}x{}, which is not a power of two",
+                    tex.meta.id, tex.data.width(), tex.data.height()
+                );
+            }
+            // the directories are loaded in the forced format already, this is for raw dumps and atlases
             if let Some(format) = force_format {
                 for tex in tp.textures.iter_mut() {
                     tex.meta.texture_format = format;
                 }
             }
+            if auto_format {
+                for tex in tp.textures.iter_mut() {
+                    tex.meta.texture_format = smb_tex::analysis::choose_format(&tex.data);
+                }
+            }
+            for &(id, format) in set_formats.iter() {
+                let mut found = false;
+                for tex in tp.textures.iter_mut().filter(|t| t.meta.id == id) {
+                    tex.meta.texture_format = format;
+                    found = true;
+                }
+                if !found {
+                    warning!("no texture {:08x} to set the format of", id);
+                }
+            }
+            if no_flip {
+                for tex in tp.textures.iter_mut() {
+                    // the raw data has the rows in the old order
+                    if !tex.meta.no_flip {
+                        tex.raw = None;
+                    }
+                    tex.meta.no_flip = true;
+                }
+            }
+            if let Some(channel_order) = channel_order {
+                for tex in tp.textures.iter_mut() {
+                    // the raw data has the channels in the old order
+                    if tex.meta.channel_order != channel_order {
+                        tex.raw = None;
+                    }
+                    tex.meta.channel_order = channel_order;
+                }
+            }
+            if let Some(mip_gamma) = mip_gamma {
+                for tex in tp.textures.iter_mut().filter(|t| t.meta.mip_gamma != mip_gamma) {
+                    tex.meta.mip_gamma = mip_gamma;
+                    tex.mips = smb_tex::mip::generate_mips_with_gamma(&tex.data, tex.meta.mip_count, mip_gamma)
+                        .with_context(|| format!("failed to generate the mip levels of texture {:08x}", tex.meta.id))?;
+                }
+            }
+            // textures written from their raw data are stored exactly as they were
+            let analyzed: Vec<_> = tp.textures.iter()
+                .filter(|t| !t.raw.as_ref().is_some_and(|raw| raw.texture_format == t.meta.texture_format))
+                .collect();
+            let analyze = |t: &&Texture| {
+                smb_tex::analysis::precision_loss(&t.data, t.meta.texture_format)
+                    .map(|loss| (t.meta.id, t.meta.texture_format, loss))
+            };
+            #[cfg(feature = "rayon")]
+            let losses = analyzed.par_iter().map(analyze).collect::<smb_tex::error::Result<Vec<_>>>()?;
+            #[cfg(not(feature = "rayon"))]
+            let losses = analyzed.iter().map(analyze).collect::<smb_tex::error::Result<Vec<_>>>()?;
+            let mut lossy = false;
+            // with --strict these are the reasons for failing, so they are printed even with --quiet
+            let warn = |message: String| if strict {
+                eprintln!("warning: {}", message);
+            } else {
+                warning!("{}", message);
+            };
+            for (id, format, loss) in losses {
+                let alpha_bits = channel_bits(format)[3];
+                if loss.loses_alpha() {
+                    let kept = match alpha_bits {
+                        0 => "has no alpha channel".to_string(),
+                        bits => format!("keeps only {} bits of alpha", bits),
+                    };
+                    warn(format!(
+                        "texture {:08x} has {} alpha levels, but {:?} {}; {} pixels will change",
+                        id, loss.alpha_levels, format, kept, loss.changed_alpha
+                    ));
+                    lossy = true;
+                }
+                if loss.loses_colors() {
+                    warn(format!(
+                        "texture {:08x} has {} distinct colors, but {:?} keeps only {} of them",
+                        id, loss.distinct_colors, format, loss.stored_colors
+                    ));
+                    lossy = true;
+                }
+            }
+            if strict && lossy {
+                bail!("some textures lose detail in their format, see the warnings above");
+            }
 
-            let data = texture::write_texture_package(&tp).unwrap();
-            std::fs::write(result, data).unwrap();
+            let mut write_options = WriteOptions::default();
+            write_options.encode.dither = dither;
+            write_options.align = align;
+            write_options.header_reserve = header_reserve;
+            write_options.row_align = row_align;
+            let size = tp.encoded_size_with_options(&write_options)?;
+            if dry_run {
+                println!("{:<8} {:>9} {:<8} {:>10} {:>10}", "id", "size", "format", "offset", "bytes");
+                for (tex, offset) in tp.textures.iter().zip(tp.data_offsets_with_options(&write_options)?) {
+                    println!(
+                        "{:08x} {:>9} {:<8} {:>#10x} {:>10}",
+                        tex.meta.id,
+                        format!("{}x{}", tex.data.width(), tex.data.height()),
+                        format!("{:?}", tex.meta.texture_format),
+                        offset,
+                        tex.encoded_size_with_row_align(row_align)?,
+                    );
+                }
+                println!();
+                println!("textures: {}", tp.textures.len());
+                println!("total size: {} bytes", size);
+                println!("formats:");
+                for &format in TextureFormat::value_variants() {
+                    let count = tp.textures.iter().filter(|t| t.meta.texture_format == format).count();
+                    println!("  {:?}: {}", format, count);
+                }
+            }
+            if size > u32::MAX as u64 {
+                bail!("the package would be {} bytes, more than the 4 GiB its 32-bit offsets can address", size);
+            }
+            if format_stats {
+                if dry_run {
+                    println!();
+                }
+                print_format_stats(&tp.textures)?;
+            }
+            if dry_run {
+                return Ok(());
+            }
+
+            let progress = Progress::new(tp.textures.len());
+            let bar = progress.clone();
+            write_options.progress = Some(std::sync::Arc::new(move || bar.inc()));
+            let data = texture::write_texture_package_with_options(&tp, &write_options)?;
+            progress.finish();
+            std::fs::write(&result, &data)
+                .with_context(|| format!("failed to write tpg file to {}", result.display()))?;
+            if with_checksum {
+                let checksum_path = checksum_path(&result);
+                let checksum = smb_tex::verify::checksum(data.as_slice())?;
+                std::fs::write(&checksum_path, format!("{:08x}\n", checksum))
+                    .with_context(|| format!("failed to write checksum to {}", checksum_path.display()))?;
+            }
+            if write_ids {
+                let mut files = Vec::new();
+                for path in paths.iter() {
+                    files.extend(texture::texture_files(path, recursive)
+                        .with_context(|| format!("failed to list the textures in {}", path.display()))?);
+                }
+                // the ids were given in the order of the files
+                for (id, file) in files.iter().enumerate() {
+                    let (meta_path, meta_format) = smb_tex::meta::find_meta(file)?;
+                    let data = std::fs::read(&meta_path)
+                        .with_context(|| format!("failed to read texture meta {}", meta_path.display()))?;
+                    let meta = smb_tex::meta::set_meta_id(&data, meta_format, id as u32)
+                        .with_context(|| format!("failed to parse texture meta {}", meta_path.display()))?;
+                    std::fs::write(&meta_path, meta)
+                        .with_context(|| format!("failed to write texture meta to {}", meta_path.display()))?;
+                }
+            }
+            if let Some(entries) = atlas_entries {
+                let coords_path = result.with_extension("atlas.json");
+                let coords = serde_json::to_string_pretty(&entries)?;
+                std::fs::write(&coords_path, coords)
+                    .with_context(|| format!("failed to write atlas coordinates to {}", coords_path.display()))?;
+            }
         }
     }
+
+    Ok(())
 }
@@ -0,0 +1,293 @@
+//! An editor-friendly form of the texture meta files
+//!
+//! The plain form of a texture's `.json` file is [`TextureMeta`] as is, with the header fields as
+//! raw integers. The pretty form has a `schema_version` and names the fields that have been
+//! figured out, with the wrap modes as strings. Both forms are accepted when loading textures.
+//!
+//! Either form can be stored as JSON or TOML, see [`MetaFormat`].
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::error::{MetaError, TextureError};
+use crate::mip::MipGamma;
+use crate::texture::{ChannelOrder, TextureFormat, TextureMeta, WrapMode};
+
+/// Version of the pretty meta form written by [`PrettyMeta::from`]
+pub const META_SCHEMA_VERSION: u32 = 1;
+
+/// Texture meta with named fields, see the module docs
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct PrettyMeta {
+    pub schema_version: u32,
+    /// Texture id, as 8 hex digits
+    #[serde(with = "hex_id")]
+    pub id: u32,
+    pub format: TextureFormat,
+    /// `"repeat"`, `"clamp"`, `"mirror"` or the raw value for unknown modes
+    #[serde(with = "wrap_mode")]
+    pub wrap_u: WrapMode,
+    #[serde(with = "wrap_mode")]
+    pub wrap_v: WrapMode,
+    pub flags: u32,
+    /// Header field whose meaning is unknown
+    pub unk_14: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    #[serde(default = "crate::texture::default_mip_count", skip_serializing_if = "crate::texture::is_default_mip_count")]
+    pub mip_count: u32,
+    #[serde(default, skip_serializing_if = "MipGamma::is_srgb")]
+    pub mip_gamma: MipGamma,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_image_hash")]
+    pub image_hash: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_flip: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub premultiplied: bool,
+    #[serde(default, skip_serializing_if = "ChannelOrder::is_rgba")]
+    pub channel_order: ChannelOrder,
+}
+
+impl From<TextureMeta> for PrettyMeta {
+    fn from(meta: TextureMeta) -> Self {
+        Self {
+            schema_version: META_SCHEMA_VERSION,
+            id: meta.id,
+            format: meta.texture_format,
+            wrap_u: meta.wrap_u(),
+            wrap_v: meta.wrap_v(),
+            flags: meta.flags().bits(),
+            unk_14: meta.unk_14,
+            order: meta.order,
+            mip_count: meta.mip_count,
+            mip_gamma: meta.mip_gamma,
+            image_hash: meta.image_hash,
+            no_flip: meta.no_flip,
+            premultiplied: meta.premultiplied,
+            channel_order: meta.channel_order,
+        }
+    }
+}
+
+impl From<PrettyMeta> for TextureMeta {
+    fn from(meta: PrettyMeta) -> Self {
+        Self {
+            id: meta.id,
+            unk_c: meta.wrap_u.to_raw(),
+            unk_10: meta.wrap_v.to_raw(),
+            unk_14: meta.unk_14,
+            unk_18: meta.flags as i32,
+            texture_format: meta.format,
+            order: meta.order,
+            mip_count: meta.mip_count,
+            mip_gamma: meta.mip_gamma,
+            image_hash: meta.image_hash,
+            no_flip: meta.no_flip,
+            premultiplied: meta.premultiplied,
+            channel_order: meta.channel_order,
+        }
+    }
+}
+
+fn check_schema_version<E: serde::de::Error>(meta: PrettyMeta) -> Result<TextureMeta, E> {
+    if meta.schema_version != META_SCHEMA_VERSION {
+        return Err(E::custom(format!(
+            "unsupported meta schema version {}, expected {}", meta.schema_version, META_SCHEMA_VERSION
+        )));
+    }
+    Ok(meta.into())
+}
+
+/// Fails with the names of the formats if the meta has none, instead of only naming the missing field
+fn require_format<E: serde::de::Error>(pretty: bool, has_field: impl Fn(&str) -> bool) -> Result<(), E> {
+    let field = if pretty { "format" } else { "texture_format" };
+    if has_field(field) {
+        return Ok(());
+    }
+    let names: Vec<_> = TextureFormat::ALL.iter().map(|format| format!("`{:?}`", format)).collect();
+    Err(E::custom(format!("missing field `{}`, which is one of {}", field, names.join(", "))))
+}
+
+/// File format of a texture's meta sidecar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum MetaFormat {
+    #[default]
+    Json,
+    /// TOML has no unsigned 64-bit integers, so the image hash is stored as a hex string
+    Toml,
+}
+
+impl MetaFormat {
+    /// Extension of sidecar files in this format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            MetaFormat::Json => "json",
+            MetaFormat::Toml => "toml",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "json" => Some(MetaFormat::Json),
+            "toml" => Some(MetaFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the sidecar of an image file, either a `.json` or a `.toml` one
+///
+/// Fails if there is none, or if there are both, as it isn't clear which one was edited last.
+pub fn find_meta(image: &Path) -> crate::error::Result<(PathBuf, MetaFormat)> {
+    let mut found = [MetaFormat::Json, MetaFormat::Toml].into_iter()
+        .map(|format| (image.with_extension(format.extension()), format))
+        .filter(|(path, _)| path.exists());
+    match (found.next(), found.next()) {
+        (Some(meta), None) => Ok(meta),
+        (Some(_), Some(_)) => Err(TextureError::AmbiguousMeta(image.to_path_buf())),
+        (None, _) => Err(TextureError::MissingMeta(image.to_path_buf())),
+    }
+}
+
+/// Serializes texture meta as the contents of a sidecar file, in the pretty form if `pretty` is set
+pub fn write_meta(meta: &TextureMeta, pretty: bool, format: MetaFormat) -> String {
+    let value = if pretty {
+        serde_json::to_value(PrettyMeta::from(*meta))
+    } else {
+        serde_json::to_value(meta)
+    }.expect("serializing texture meta can't fail");
+    match format {
+        MetaFormat::Json => serde_json::to_string_pretty(&value).expect("serializing texture meta can't fail"),
+        MetaFormat::Toml => {
+            let mut value = value;
+            if let Some(hash) = value.get_mut("image_hash") {
+                *hash = format!("{:016x}", hash.as_u64().expect("the image hash is a u64")).into();
+            }
+            toml::to_string(&value).expect("the meta has no values TOML can't store")
+        }
+    }
+}
+
+/// Parses the contents of a sidecar file in either meta form, telling them apart by the `schema_version` field
+///
+/// The meta is deserialized from the text itself rather than from a parsed value, so that the
+/// errors say which line the problem is on.
+pub fn read_meta(data: &[u8], format: MetaFormat) -> Result<TextureMeta, MetaError> {
+    Ok(parse_meta(data, format)?.0)
+}
+
+/// Rewrites the contents of a sidecar file with another texture id, keeping its form
+pub fn set_meta_id(data: &[u8], format: MetaFormat, id: u32) -> Result<String, MetaError> {
+    let (mut meta, pretty) = parse_meta(data, format)?;
+    meta.id = id;
+    Ok(write_meta(&meta, pretty, format))
+}
+
+/// Parses the contents of a sidecar file, along with whether it is in the pretty form
+fn parse_meta(data: &[u8], format: MetaFormat) -> Result<(TextureMeta, bool), MetaError> {
+    match format {
+        MetaFormat::Json => {
+            let value: serde_json::Value = serde_json::from_slice(data)?;
+            let pretty = value.get("schema_version").is_some();
+            if value.is_object() {
+                require_format::<serde_json::Error>(pretty, |field| value.get(field).is_some())?;
+            }
+            let meta = if pretty {
+                check_schema_version::<serde_json::Error>(serde_json::from_slice(data)?)?
+            } else {
+                serde_json::from_slice(data)?
+            };
+            Ok((meta, pretty))
+        }
+        MetaFormat::Toml => {
+            let text = std::str::from_utf8(data).map_err(<toml::de::Error as serde::de::Error>::custom)?;
+            let table: toml::Table = toml::from_str(text)?;
+            let pretty = table.contains_key("schema_version");
+            require_format::<toml::de::Error>(pretty, |field| table.contains_key(field))?;
+            let meta = if pretty {
+                check_schema_version::<toml::de::Error>(toml::from_str(text)?)?
+            } else {
+                toml::from_str(text)?
+            };
+            Ok((meta, pretty))
+        }
+    }
+}
+
+/// Deserializes an image hash stored as an integer, or as a hex string as in TOML files
+pub(crate) fn deserialize_image_hash<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Int(u64),
+        Hex(String),
+    }
+
+    match Option::<Repr>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Repr::Int(hash)) => Ok(Some(hash)),
+        Some(Repr::Hex(digits)) => u64::from_str_radix(&digits, 16)
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("{:?} is not a hex image hash", digits))),
+    }
+}
+
+/// Deserializes texture meta in either form, for use with `#[serde(deserialize_with)]`
+pub fn deserialize_meta<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TextureMeta, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    read_meta(value.to_string().as_bytes(), MetaFormat::Json).map_err(serde::de::Error::custom)
+}
+
+mod hex_id {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:08x}", id))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        let digits = id.strip_prefix("0x").unwrap_or(&id);
+        u32::from_str_radix(digits, 16)
+            .map_err(|_| serde::de::Error::custom(format!("{:?} is not a hex texture id", id)))
+    }
+}
+
+mod wrap_mode {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Named(Name),
+        Raw(i32),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Name {
+        Repeat,
+        Clamp,
+        Mirror,
+    }
+
+    pub fn serialize<S: Serializer>(mode: &WrapMode, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match *mode {
+            WrapMode::Repeat => Repr::Named(Name::Repeat),
+            WrapMode::Clamp => Repr::Named(Name::Clamp),
+            WrapMode::Mirror => Repr::Named(Name::Mirror),
+            WrapMode::Unknown(value) => Repr::Raw(value),
+        };
+        repr.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WrapMode, D::Error> {
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Named(Name::Repeat) => WrapMode::Repeat,
+            Repr::Named(Name::Clamp) => WrapMode::Clamp,
+            Repr::Named(Name::Mirror) => WrapMode::Mirror,
+            // raw values of known modes are accepted too
+            Repr::Raw(value) => WrapMode::from_raw(value),
+        })
+    }
+}
@@ -0,0 +1,113 @@
+//! Generating mip chains for textures
+//!
+//! The package format has no field we know of that stores a mip count, so mip levels can't be
+//! detected when reading a package and only the base level is read. Mip levels given in
+//! [`Texture::mips`](crate::texture::Texture::mips) are written right after the base level.
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::error::{Result, TextureError};
+
+/// Color space the pixels are averaged in when generating mip levels
+///
+/// The texture data is sRGB-encoded, so averaging the stored values darkens mixes of light and
+/// dark pixels compared to what the GPU does when sampling. The alpha channel is linear either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum MipGamma {
+    /// Average the sRGB-encoded values as stored, which is faster but darkens the mip levels
+    #[default]
+    Srgb,
+    /// Convert the colors to linear light, average them and convert them back to sRGB
+    Linear,
+}
+
+impl MipGamma {
+    pub fn is_srgb(&self) -> bool {
+        *self == MipGamma::Srgb
+    }
+}
+
+/// Maximum number of mip levels (including the base one) of a texture with the given dimensions
+pub fn max_mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+/// Downsamples an image to half its size (but not below 1 pixel) by averaging 2x2 blocks
+pub fn downsample(image: &RgbaImage) -> RgbaImage {
+    downsample_with_gamma(image, MipGamma::Srgb)
+}
+
+/// Same as [`downsample`], averaging the colors in the given color space
+pub fn downsample_with_gamma(image: &RgbaImage, gamma: MipGamma) -> RgbaImage {
+    let to_linear: [f64; 256] = std::array::from_fn(|value| srgb_to_linear(value as u8));
+    let (width, height) = image.dimensions();
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let x0 = 2 * x;
+        let y0 = 2 * y;
+        let x1 = (x0 + 1).min(width - 1);
+        let y1 = (y0 + 1).min(height - 1);
+        let pixels = [
+            image.get_pixel(x0, y0),
+            image.get_pixel(x1, y0),
+            image.get_pixel(x0, y1),
+            image.get_pixel(x1, y1),
+        ];
+
+        let mut result = [0; 4];
+        for (c, value) in result.iter_mut().enumerate() {
+            *value = if gamma == MipGamma::Linear && c < 3 {
+                let sum: f64 = pixels.iter().map(|p| to_linear[p[c] as usize]).sum();
+                linear_to_srgb(sum / 4.0)
+            } else {
+                let sum: u32 = pixels.iter().map(|p| p[c] as u32).sum();
+                ((sum + 2) / 4) as u8
+            };
+        }
+        Rgba(result)
+    })
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Generates the mip levels below `base`, so that there are `count` levels including the base one
+pub fn generate_mips(base: &RgbaImage, count: u32) -> Result<Vec<RgbaImage>> {
+    generate_mips_with_gamma(base, count, MipGamma::Srgb)
+}
+
+/// Same as [`generate_mips`], averaging the colors in the given color space
+pub fn generate_mips_with_gamma(base: &RgbaImage, count: u32, gamma: MipGamma) -> Result<Vec<RgbaImage>> {
+    let max_count = max_mip_count(base.width(), base.height());
+    if count == 0 || count > max_count {
+        return Err(TextureError::InvalidMipCount {
+            width: base.width(),
+            height: base.height(),
+            count,
+            max: max_count,
+        });
+    }
+
+    let mut mips: Vec<RgbaImage> = Vec::new();
+    for _ in 1..count {
+        let next = downsample_with_gamma(mips.last().unwrap_or(base), gamma);
+        mips.push(next);
+    }
+    Ok(mips)
+}
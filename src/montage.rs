@@ -0,0 +1,126 @@
+//! Rendering an overview image of all the textures in a package
+//!
+//! The textures are scaled to fit thumbnails of the same size and laid out in a grid, with the id
+//! of each one written under it. Transparent parts are shown over a checkerboard.
+
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+use crate::texture::Texture;
+
+/// Space around the thumbnails, in pixels
+const PADDING: u32 = 4;
+/// Glyphs are 3x5 pixels before scaling, with a pixel of space after each
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+const BACKGROUND: Rgba<u8> = Rgba([0x20, 0x20, 0x20, 0xFF]);
+const LABEL_COLOR: Rgba<u8> = Rgba([0xFF, 0xFF, 0xFF, 0xFF]);
+const CHECKER_SIZE: u32 = 8;
+const CHECKER_COLORS: [[u8; 3]; 2] = [[0x99, 0x99, 0x99], [0x66, 0x66, 0x66]];
+
+/// Rows of the hex digit glyphs, the highest of the 3 bits being the leftmost pixel
+const GLYPHS: [[u8; 5]; 16] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+    [0b010, 0b101, 0b111, 0b101, 0b101],
+    [0b110, 0b101, 0b110, 0b101, 0b110],
+    [0b011, 0b100, 0b100, 0b100, 0b011],
+    [0b110, 0b101, 0b101, 0b101, 0b110],
+    [0b111, 0b100, 0b111, 0b100, 0b111],
+    [0b111, 0b100, 0b111, 0b100, 0b100],
+];
+
+/// Lays out the base levels of the textures in a grid of `thumbnail_size` sized cells, in package order
+///
+/// Textures are scaled to fit their cell, keeping their aspect ratio. With `columns` 0 the grid is
+/// about as wide as it is tall.
+pub fn montage(textures: &[Texture], thumbnail_size: u32, columns: u32) -> RgbaImage {
+    let thumbnail_size = thumbnail_size.max(1);
+    let count = textures.len() as u32;
+    let columns = match columns {
+        0 => (count as f64).sqrt().ceil() as u32,
+        columns => columns,
+    }.clamp(1, count.max(1));
+    let rows = count.div_ceil(columns);
+
+    // the widest scale at which the 8 digits of an id fit under a thumbnail
+    let label_scale = (thumbnail_size / (8 * (GLYPH_WIDTH + 1))).max(1);
+    let label_height = GLYPH_HEIGHT * label_scale + PADDING;
+    let label_width = 8 * (GLYPH_WIDTH + 1) * label_scale - label_scale;
+    // tiny thumbnails get cells wide enough for their labels
+    let inner_width = thumbnail_size.max(label_width);
+    let cell_width = inner_width + PADDING;
+    let cell_height = thumbnail_size + label_height + PADDING;
+
+    let mut result = RgbaImage::from_pixel(
+        columns * cell_width + PADDING,
+        rows * cell_height + PADDING,
+        BACKGROUND,
+    );
+    for (index, texture) in textures.iter().enumerate() {
+        let index = index as u32;
+        let x = PADDING + index % columns * cell_width;
+        let y = PADDING + index / columns * cell_height;
+
+        let thumbnail = thumbnail(&texture.data, thumbnail_size);
+        // centered in the cell
+        let left = x + (inner_width - thumbnail.width()) / 2;
+        let top = y + (thumbnail_size - thumbnail.height()) / 2;
+        for (tx, ty, pixel) in thumbnail.enumerate_pixels() {
+            let checker = CHECKER_COLORS[((tx / CHECKER_SIZE + ty / CHECKER_SIZE) % 2) as usize];
+            result.put_pixel(left + tx, top + ty, over(*pixel, checker));
+        }
+
+        let label = format!("{:08x}", texture.meta.id);
+        let label_x = x + (inner_width - label_width) / 2;
+        draw_text(&mut result, &label, label_x, y + thumbnail_size + PADDING, label_scale);
+    }
+    result
+}
+
+/// Scales an image to fit a `size` by `size` square, keeping its aspect ratio
+fn thumbnail(image: &RgbaImage, size: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return RgbaImage::new(0, 0);
+    }
+    let longest = width.max(height) as u64;
+    let scaled_width = ((width as u64 * size as u64 / longest) as u32).max(1);
+    let scaled_height = ((height as u64 * size as u64 / longest) as u32).max(1);
+    // keep the pixels sharp when enlarging small textures
+    let filter = if longest < size as u64 { FilterType::Nearest } else { FilterType::Triangle };
+    image::imageops::resize(image, scaled_width, scaled_height, filter)
+}
+
+/// Blends a pixel over an opaque background color
+fn over(pixel: Rgba<u8>, background: [u8; 3]) -> Rgba<u8> {
+    let alpha = pixel[3] as u32;
+    let blend = |c: usize| ((pixel[c] as u32 * alpha + background[c] as u32 * (255 - alpha) + 127) / 255) as u8;
+    Rgba([blend(0), blend(1), blend(2), 0xFF])
+}
+
+/// Draws a string of hex digits with its top left corner at `x`, `y`
+fn draw_text(image: &mut RgbaImage, text: &str, x: u32, y: u32, scale: u32) {
+    for (index, digit) in text.chars().filter_map(|c| c.to_digit(16)).enumerate() {
+        let glyph_x = x + index as u32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in GLYPHS[digit as usize].iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        image.put_pixel(glyph_x + column * scale + dx, y + row as u32 * scale + dy, LABEL_COLOR);
+                    }
+                }
+            }
+        }
+    }
+}
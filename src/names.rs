@@ -0,0 +1,259 @@
+//! Mapping texture ids to descriptive file names
+//!
+//! A name map is a JSON object with hex texture ids as keys and file names (without the extension)
+//! as values, for example `{"0000000a": "menu_background"}`.
+//!
+//! Textures missing from the map are named after their id, see [`NameTemplate`] for other ways of
+//! writing it than 8 hex digits.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::error::{Result, TextureError};
+use crate::texture::TextureFormat;
+
+#[derive(Debug, Clone, Default)]
+pub struct NameMap {
+    names: BTreeMap<u32, String>,
+}
+
+impl NameMap {
+    /// Builds a name map, checking that the names are unique and usable as file names
+    pub fn new(names: BTreeMap<u32, String>) -> Result<Self> {
+        let mut seen = BTreeMap::new();
+        for (&id, name) in names.iter() {
+            if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+                return Err(TextureError::InvalidNameMap(format!("{:?} is not a valid file name", name)));
+            }
+            if let Some(other) = seen.insert(name.as_str(), id) {
+                return Err(TextureError::InvalidNameMap(format!(
+                    "textures {:08x} and {:08x} are both named {:?}", other, id, name
+                )));
+            }
+        }
+        Ok(Self { names })
+    }
+
+    /// Returns the file name of a texture, falling back to its hex id
+    pub fn name(&self, id: u32) -> String {
+        match self.names.get(&id) {
+            Some(name) => name.clone(),
+            None => format!("{:08x}", id),
+        }
+    }
+
+    /// Returns the file name the map gives a texture, if any
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Finds the id of the texture with the given file name, if it is in the map
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.names.iter().find(|(_, n)| *n == name).map(|(&id, _)| id)
+    }
+}
+
+pub fn read_name_map(path: &Path) -> Result<NameMap> {
+    let raw: BTreeMap<String, String> = serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|source| TextureError::InvalidNameMapFile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let mut names = BTreeMap::new();
+    for (id, name) in raw {
+        let digits = id.strip_prefix("0x").unwrap_or(&id);
+        let id = u32::from_str_radix(digits, 16)
+            .map_err(|_| TextureError::InvalidNameMap(format!("{:?} is not a hex texture id", id)))?;
+        names.insert(id, name);
+    }
+    NameMap::new(names)
+}
+
+/// Hash turning file names into texture ids, see [`crate::texture::DirectoryOptions::id_from_name`]
+///
+/// Which hash the game uses for names, if any, isn't known, so these are common ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum NameHash {
+    /// CRC-32 (IEEE) of the UTF-8 bytes of the name, as computed by zlib
+    Crc32,
+    /// 32-bit FNV-1a of the UTF-8 bytes of the name
+    Fnv1a,
+}
+
+impl NameHash {
+    pub fn id(self, name: &str) -> u32 {
+        self.function()(name)
+    }
+
+    /// The hash as a plain function, for [`crate::texture::DirectoryOptions::id_from_name`]
+    pub fn function(self) -> fn(&str) -> u32 {
+        match self {
+            NameHash::Crc32 => id_for_name,
+            NameHash::Fnv1a => fnv1a,
+        }
+    }
+}
+
+/// Id of the texture with the given file name (without the extension), its CRC-32
+pub fn id_for_name(name: &str) -> u32 {
+    crate::verify::checksum(name.as_bytes()).expect("reading from a slice can't fail")
+}
+
+fn fnv1a(name: &str) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+/// Pattern of the file names of textures, like `{id:08x}` or `{id}_{format}`
+///
+/// The placeholders are `{id}` for the decimal id, `{id:x}` for the hex id without leading zeros,
+/// `{id:08x}` for the hex id padded to 8 digits and `{format}` for the lowercase format name, as
+/// accepted by `--format`. `{index}` is the position of the texture in the package, `{index:03}`
+/// pads it with zeros to the given number of digits. A template has exactly one id placeholder, so
+/// that the ids can be parsed back from the file names with [`NameTemplate::id`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdStyle {
+    Decimal,
+    Hex,
+    PaddedHex,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Id(IdStyle),
+    /// The index, padded to the given number of digits
+    Index(usize),
+    Format,
+}
+
+impl Default for NameTemplate {
+    /// The hex ids used when there is no template
+    fn default() -> Self {
+        Self { parts: vec![TemplatePart::Id(IdStyle::PaddedHex)] }
+    }
+}
+
+impl NameTemplate {
+    /// Parses a template, checking its placeholders and that the names are usable as file names
+    pub fn new(template: &str) -> Result<Self> {
+        let invalid = |reason: String| TextureError::InvalidNameTemplate(format!("{:?} {}", template, reason));
+        if !balanced(template) {
+            return Err(invalid("has a `}` without a matching `{`".to_string()));
+        }
+        if template.contains(['/', '\\']) {
+            return Err(invalid("contains a path separator".to_string()));
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                parts.push(TemplatePart::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}')
+                .ok_or_else(|| invalid("has a `{` without a matching `}`".to_string()))? + start;
+            let placeholder = &rest[start + 1..end];
+            let index_width = placeholder.strip_prefix("index:0")
+                .filter(|width| (1..=2).contains(&width.len()))
+                .and_then(|width| width.parse().ok());
+            parts.push(match placeholder {
+                "id" => TemplatePart::Id(IdStyle::Decimal),
+                "id:x" => TemplatePart::Id(IdStyle::Hex),
+                "id:08x" => TemplatePart::Id(IdStyle::PaddedHex),
+                "index" => TemplatePart::Index(0),
+                "format" => TemplatePart::Format,
+                other => match index_width {
+                    Some(width) => TemplatePart::Index(width),
+                    None => return Err(invalid(format!(
+                        "has unknown placeholder {{{}}}, expected {{id}}, {{id:x}}, {{id:08x}}, {{index}}, {{index:03}} or {{format}}",
+                        other
+                    ))),
+                },
+            });
+            rest = &rest[end + 1..];
+        }
+
+        match parts.iter().filter(|p| matches!(p, TemplatePart::Id(_))).count() {
+            1 => Ok(Self { parts }),
+            0 => Err(invalid("has no id placeholder, so the textures would not get distinct names".to_string())),
+            _ => Err(invalid("has more than one id placeholder".to_string())),
+        }
+    }
+
+    /// Returns the file name of the texture at `index` in its package, without the extension
+    pub fn name(&self, id: u32, index: u32, format: TextureFormat) -> String {
+        self.parts.iter().map(|part| match part {
+            TemplatePart::Literal(text) => text.clone(),
+            TemplatePart::Id(IdStyle::Decimal) => id.to_string(),
+            TemplatePart::Id(IdStyle::Hex) => format!("{:x}", id),
+            TemplatePart::Id(IdStyle::PaddedHex) => format!("{:08x}", id),
+            TemplatePart::Index(width) => format!("{:0width$}", index, width = *width),
+            TemplatePart::Format => format.name().to_string(),
+        }).collect()
+    }
+
+    /// Parses the id back from a file name (without the extension) following the template
+    pub fn id(&self, name: &str) -> Option<u32> {
+        match_parts(&self.parts, name).flatten()
+    }
+}
+
+/// Whether every `}` of a template closes a `{`
+fn balanced(template: &str) -> bool {
+    let mut open = false;
+    for c in template.chars() {
+        match c {
+            '{' => open = true,
+            '}' if !open => return false,
+            '}' => open = false,
+            _ => {}
+        }
+    }
+    true
+}
+
+
+/// Matches the rest of a name against the rest of the template, returning the id if it is in that part
+fn match_parts(parts: &[TemplatePart], name: &str) -> Option<Option<u32>> {
+    let Some((part, parts)) = parts.split_first() else {
+        return name.is_empty().then_some(None);
+    };
+    match part {
+        TemplatePart::Literal(text) => match_parts(parts, name.strip_prefix(text.as_str())?),
+        TemplatePart::Format => TextureFormat::ALL.iter()
+            .find_map(|&format| match_parts(parts, name.strip_prefix(format.name())?)),
+        TemplatePart::Index(width) => {
+            let digits = name.find(|c: char| !c.is_ascii_digit()).unwrap_or(name.len());
+            ((*width).max(1)..=digits).rev().find_map(|length| match_parts(parts, &name[length..]))
+        }
+        TemplatePart::Id(style) => {
+            let radix = if *style == IdStyle::Decimal { 10 } else { 16 };
+            let digits = name.find(|c: char| !c.is_digit(radix)).unwrap_or(name.len());
+            let lengths = match style {
+                IdStyle::PaddedHex if digits >= 8 => 8..=8,
+                IdStyle::PaddedHex => return None,
+                _ => 1..=digits,
+            };
+            // the digits may be followed by literal text starting with a digit, so try every split
+            lengths.rev().find_map(|length| {
+                let (digits, rest) = name.split_at(length);
+                match_parts(parts, rest)?;
+                u32::from_str_radix(digits, radix).ok().map(Some)
+            })
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Serde forms of the image and byte fields of [`crate::texture::Texture`], as base64 strings
+//!
+//! Only built with the `package-serde` feature. Images are stored as RGBA8 PNG files, which
+//! decode back to the exact same pixels.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::RgbaImage;
+use serde::{Deserialize, Deserializer, Serializer};
+
+fn decode_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    STANDARD.decode(text).map_err(serde::de::Error::custom)
+}
+
+fn encode_image(image: &RgbaImage) -> Result<String, String> {
+    let png = crate::texture::encode_png(image).map_err(|err| err.to_string())?;
+    Ok(STANDARD.encode(png))
+}
+
+fn decode_image(png: &[u8]) -> Result<RgbaImage, String> {
+    image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .map(|image| image.into_rgba8())
+        .map_err(|err| err.to_string())
+}
+
+pub(crate) mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        decode_base64(deserializer)
+    }
+}
+
+pub(crate) mod png {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(image: &RgbaImage, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_image(image).map_err(serde::ser::Error::custom)?)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RgbaImage, D::Error> {
+        decode_image(&decode_base64(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+pub(crate) mod png_vec {
+    use serde::Serialize;
+    use super::*;
+
+    pub fn serialize<S: Serializer>(images: &[RgbaImage], serializer: S) -> Result<S::Ok, S::Error> {
+        let encoded = images.iter()
+            .map(encode_image)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(serde::ser::Error::custom)?;
+        encoded.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<RgbaImage>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|text| {
+                let png = STANDARD.decode(text).map_err(serde::de::Error::custom)?;
+                decode_image(&png).map_err(serde::de::Error::custom)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,125 @@
+//! The palettized P8 texture format
+//!
+//! The data of a P8 texture starts with a palette of [`PALETTE_LEN`] RGBA8 colors, followed by one
+//! byte per pixel indexing into it. Images with more colors than fit into the palette are reduced
+//! with median cut.
+
+use std::collections::HashMap;
+use image::RgbaImage;
+use crate::error::Result;
+
+/// Number of colors in the palette of a P8 texture
+pub const PALETTE_LEN: usize = 256;
+/// Size of the palette at the start of the data of a P8 texture, in bytes
+pub const PALETTE_SIZE: usize = PALETTE_LEN * 4;
+
+/// Picks at most `max_colors` colors representing the image
+///
+/// Images with few enough distinct colors get exactly those. Otherwise the colors are split with
+/// median cut: the box of pixels with the widest channel range is repeatedly split at the median
+/// of that channel, and each box is represented by the average of its pixels.
+pub fn build_palette(image: &RgbaImage, max_colors: usize) -> Vec<[u8; 4]> {
+    let mut distinct: Vec<_> = image.pixels().map(|p| p.0).collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if distinct.len() <= max_colors {
+        return distinct;
+    }
+
+    let mut boxes = vec![ColorBox::new(image.pixels().map(|p| p.0).collect())];
+    while boxes.len() < max_colors {
+        // (box index, channel, range) of the widest channel of all the boxes
+        let widest = boxes.iter()
+            .enumerate()
+            .flat_map(|(index, b)| (0..4).map(move |c| (index, c, b.ranges[c])))
+            .max_by_key(|&(_, _, range)| range);
+        let (index, channel) = match widest {
+            Some((index, channel, range)) if range > 0 => (index, channel),
+            // every box has a single color left
+            _ => break,
+        };
+
+        let mut pixels = boxes.swap_remove(index).pixels;
+        pixels.sort_unstable_by_key(|p| p[channel]);
+        // split between different values, so that both halves are non-empty
+        let median = pixels[pixels.len() / 2][channel];
+        let mut split = pixels.partition_point(|p| p[channel] < median);
+        if split == 0 {
+            split = pixels.partition_point(|p| p[channel] <= median);
+        }
+        let upper = pixels.split_off(split);
+        boxes.push(ColorBox::new(pixels));
+        boxes.push(ColorBox::new(upper));
+    }
+
+    boxes.iter().map(|b| average(&b.pixels)).collect()
+}
+
+/// Pixels of a median cut box, with the range of values of each channel
+struct ColorBox {
+    pixels: Vec<[u8; 4]>,
+    ranges: [u8; 4],
+}
+
+impl ColorBox {
+    fn new(pixels: Vec<[u8; 4]>) -> Self {
+        let ranges = [0, 1, 2, 3].map(|c| {
+            let values = pixels.iter().map(|p| p[c]);
+            values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+        });
+        Self { pixels, ranges }
+    }
+}
+
+fn average(pixels: &[[u8; 4]]) -> [u8; 4] {
+    let mut sums = [0u64; 4];
+    for pixel in pixels {
+        for c in 0..4 {
+            sums[c] += pixel[c] as u64;
+        }
+    }
+    let count = pixels.len() as u64;
+    sums.map(|sum| ((sum + count / 2) / count) as u8)
+}
+
+fn nearest(palette: &[[u8; 4]], color: [u8; 4]) -> u8 {
+    let distance = |entry: &[u8; 4]| -> u32 {
+        (0..4).map(|c| (entry[c] as i32 - color[c] as i32).pow(2) as u32).sum()
+    };
+    palette.iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+/// Encodes an image as the data of a P8 texture, with the rows flipped unless `no_flip` is set
+pub(crate) fn encode_p8(image: &RgbaImage, no_flip: bool) -> Vec<u8> {
+    let palette = build_palette(image, PALETTE_LEN);
+    let mut data = Vec::with_capacity(PALETTE_SIZE + image.width() as usize * image.height() as usize);
+    for entry in palette.iter() {
+        data.extend_from_slice(entry);
+    }
+    data.resize(PALETTE_SIZE, 0);
+
+    let mut indices = HashMap::new();
+    let mut rows: Vec<_> = image.rows().collect();
+    // the textures are stored upside-down, see `decode_texture`
+    if !no_flip {
+        rows.reverse();
+    }
+    for row in rows {
+        for pixel in row {
+            data.push(*indices.entry(pixel.0).or_insert_with(|| nearest(&palette, pixel.0)));
+        }
+    }
+    data
+}
+
+/// Decodes the data of a P8 texture, whose size has already been checked, keeping the row order
+pub(crate) fn decode_p8(data: &[u8], width: u32, height: u32) -> Result<RgbaImage> {
+    let (palette, indices) = data.split_at(PALETTE_SIZE);
+    let pixels = indices.iter()
+        .flat_map(|&index| palette[index as usize * 4..index as usize * 4 + 4].iter().copied())
+        .collect();
+    crate::texture::image_from_pixels(width, height, pixels)
+}
@@ -0,0 +1,93 @@
+//! Storing decoded textures as a single blob of RGBA8 pixels with a table of contents
+//!
+//! This is meant for tools that load all the textures at once, where opening a file per texture is slow.
+
+use std::path::Path;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use crate::error::{Result, TextureError};
+use crate::texture::{DirectoryOptions, Texture, TextureMeta, TexturePackage};
+
+/// Name of the table of contents in a raw dump directory
+pub const TOC_FILE_NAME: &str = "toc.json";
+/// Name of the pixel data blob in a raw dump directory
+pub const DATA_FILE_NAME: &str = "textures.raw";
+
+/// Location of a single texture in the pixel data blob
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TocEntry {
+    /// Offset of the texture's pixels in the blob, the rows are stored top-down
+    pub offset: u64,
+    pub width: u32,
+    pub height: u32,
+    #[serde(flatten)]
+    pub meta: TextureMeta,
+}
+
+/// Writes the textures into `dir` as a pixel data blob and a table of contents, in the given order
+pub fn write_raw_dump(dir: &Path, textures: &[(TextureMeta, RgbaImage)]) -> Result<()> {
+    let mut toc = Vec::new();
+    let mut data = Vec::new();
+    for (meta, image) in textures {
+        toc.push(TocEntry {
+            offset: data.len() as u64,
+            width: image.width(),
+            height: image.height(),
+            meta: *meta,
+        });
+        data.extend_from_slice(image.as_raw());
+    }
+
+    std::fs::write(dir.join(DATA_FILE_NAME), data)?;
+    let toc = serde_json::to_string_pretty(&toc).expect("serializing the toc can't fail");
+    std::fs::write(dir.join(TOC_FILE_NAME), toc)?;
+    Ok(())
+}
+
+/// Loads the textures of a raw dump written by [`write_raw_dump`], in the order of the table of contents
+///
+/// Of the options only resizing and the dimension checks are used, as the texture ids are already unique in the package the dump came from.
+pub fn read_raw_dump(dir: &Path, options: &DirectoryOptions) -> Result<TexturePackage> {
+    let toc_path = dir.join(TOC_FILE_NAME);
+    let toc: Vec<TocEntry> = serde_json::from_slice(&std::fs::read(&toc_path)?)
+        .map_err(|source| TextureError::InvalidRawToc {
+            path: toc_path.clone(),
+            source,
+        })?;
+    let data = std::fs::read(dir.join(DATA_FILE_NAME))?;
+
+    let mut textures = Vec::new();
+    for entry in toc {
+        let size = entry.width as u64 * entry.height as u64 * 4;
+        let available = (data.len() as u64).saturating_sub(entry.offset);
+        if available < size {
+            return Err(TextureError::TruncatedData {
+                expected: size,
+                actual: available,
+            });
+        }
+        let pixels = data[entry.offset as usize..(entry.offset + size) as usize].to_vec();
+        let image = crate::texture::image_from_pixels(entry.width, entry.height, pixels)?;
+        let mips = crate::mip::generate_mips_with_gamma(&image, entry.meta.mip_count, entry.meta.mip_gamma)?;
+        let mut texture = Texture {
+            meta: entry.meta,
+            data: image,
+            mips,
+            raw: None,
+        };
+        let resized = match options.resize {
+            Some(resize) => texture.resize(&resize),
+            None => Ok(()),
+        };
+        resized.and_then(|()| texture.check_dimensions(options)).map_err(|err| TextureError::Load {
+            path: toc_path.clone(),
+            source: Box::new(err),
+        })?;
+        textures.push(texture);
+    }
+
+    Ok(TexturePackage {
+        textures_ptr: None,
+        textures,
+    })
+}
@@ -1,66 +1,470 @@
 use std::fmt::Debug;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::Path;
-use anyhow::{bail, Result};
-use binrw::{BinRead, binread, BinReaderExt, BinResult, BinrwNamedArgs, BinWrite, FilePtr32, ReadOptions};
-use image::{DynamicImage, RgbaImage, RgbImage};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::error::{Result, TextureError};
+use crate::names::{NameMap, NameTemplate};
+use crate::mip::MipGamma;
+use binrw::{BinRead, binread, BinReaderExt, BinResult, BinrwNamedArgs, BinWrite, Endian, FilePtr32, ReadOptions as BinReadOptions};
+use image::RgbaImage;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
+/// A decoded package, see [`read_texture_package`] and [`write_texture_package`]
+///
+/// With the `package-serde` feature this can also be stored with serde, for example as the
+/// project file of an editor. That form has the images as base64 PNG files and reproduces the
+/// package exactly, it is not meant to be read by the game.
 #[binread]
 #[derive(Debug)]
+#[cfg_attr(feature = "package-serde", derive(Serialize, Deserialize))]
 pub struct TexturePackage {
     #[br(temp)]
     texture_count: u32,
+    /// Offset of the texture headers in the package this was read from
+    ///
+    /// When set, [`write_texture_package`] puts the texture headers there instead of after the
+    /// package header and [`WriteOptions::header_reserve`], so a package read and written back
+    /// keeps its header as is. Packages made from scratch leave this `None`.
+    #[br(restore_position, map = Some)]
+    pub textures_ptr: Option<u32>,
     #[br(parse_with = FilePtr32::parse, count = texture_count)]
     pub textures: Vec<Texture>,
 }
 
+/// Options controlling how [`TexturePackage::from_directory_with_options`] loads textures
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryOptions {
+    /// Accept several textures with the same id instead of failing
+    pub allow_duplicate_ids: bool,
+    /// Fail if the width or height of a texture is larger than this
+    pub max_dimension: Option<u32>,
+    /// Fail if the width or height of a texture is not a power of two
+    pub require_pow2: bool,
+    /// Take the ids of the textures whose file names are in the map from it, instead of their meta
+    pub names: NameMap,
+    /// Take the ids of the textures whose file names follow the template from their names, unless the name map has them
+    pub name_template: Option<NameTemplate>,
+    /// Resize all textures to these dimensions before checking them
+    pub resize: Option<Resize>,
+    /// Also load the textures in subdirectories, manifest entries then name them by their relative path
+    pub recursive: bool,
+    /// Take the ids of the textures not in the name map from their file names (without the extension)
+    ///
+    /// The function is usually a [`crate::names::NameHash`], like [`crate::names::id_for_name`].
+    /// Two names hashing to the same id are reported as duplicate ids.
+    pub id_from_name: Option<fn(&str) -> u32>,
+    /// Number the textures from 0 in the order of their files, replacing the ids of their meta
+    pub sequential_ids: bool,
+    /// Store all textures in this format instead of the one in their meta
+    ///
+    /// Unlike changing the format after loading, images with 16-bit channels are reduced straight
+    /// to the bits of this format rather than to 8 bits first, see [`from_dynamic_image`].
+    pub force_format: Option<TextureFormat>,
+}
+
+/// Target dimensions and filter for [`Texture::resize`]
+#[derive(Debug, Clone, Copy)]
+pub struct Resize {
+    pub width: u32,
+    pub height: u32,
+    pub filter: image::imageops::FilterType,
+}
+
 impl TexturePackage {
+    /// Loads textures from a directory of `.png` or `.ktx2` files with `.json` sidecars
+    ///
+    /// Textures that have an `order` in their meta come first, sorted by it, so that an extracted
+    /// package is recreated with the textures in their original slots. The rest are sorted by id
+    /// (and by file name for textures sharing an id), so the result does not depend on the order
+    /// the filesystem lists the files in.
     pub fn from_directory(path: &Path) -> Result<Self> {
+        Self::from_directory_with_options(path, &DirectoryOptions::default())
+    }
+
+    pub fn from_directory_with_options(path: &Path, options: &DirectoryOptions) -> Result<Self> {
+        Self::from_loaded(Self::load_directory(path, None, options)?, options)
+    }
+
+    /// Loads the textures of several directories into one package
+    ///
+    /// Duplicate ids are checked across all the directories, and the textures are sorted as by
+    /// [`TexturePackage::from_directory`], textures sharing an id keeping the order of the directories.
+    pub fn from_directories_with_options(paths: &[PathBuf], options: &DirectoryOptions) -> Result<Self> {
+        let mut textures = Vec::new();
+        for path in paths {
+            textures.extend(Self::load_directory(path, None, options)?);
+        }
+        Self::from_loaded(textures, options)
+    }
+
+    /// Loads textures from a directory of `.png` or `.ktx2` files, taking their meta from a manifest
+    ///
+    /// Textures not listed in the manifest still use their `.json` sidecars.
+    pub fn from_directory_with_manifest(path: &Path, manifest: &[ManifestEntry], options: &DirectoryOptions) -> Result<Self> {
+        Self::from_loaded(Self::load_directory(path, Some(manifest), options)?, options)
+    }
+
+    /// Starts building a package in code, see [`TexturePackageBuilder`]
+    pub fn builder() -> TexturePackageBuilder {
+        TexturePackageBuilder::default()
+    }
+
+    /// Offsets at which [`write_texture_package`] would place the data of each texture
+    pub fn data_offsets(&self) -> Result<Vec<u64>> {
+        self.data_offsets_with_options(&WriteOptions::default())
+    }
+
+    /// Same as [`TexturePackage::data_offsets`], for [`write_texture_package_with_options`]
+    pub fn data_offsets_with_options(&self, options: &WriteOptions) -> Result<Vec<u64>> {
+        Ok(self.layout(options)?.0)
+    }
+
+    /// Size of the package file [`write_texture_package`] would produce, computed without encoding the textures
+    pub fn encoded_size(&self) -> Result<u64> {
+        self.encoded_size_with_options(&WriteOptions::default())
+    }
+
+    /// Same as [`TexturePackage::encoded_size`], for [`write_texture_package_with_options`]
+    pub fn encoded_size_with_options(&self, options: &WriteOptions) -> Result<u64> {
+        Ok(self.layout(options)?.1)
+    }
+
+    /// Offset of the first texture header, see [`TexturePackage::textures_ptr`]
+    fn textures_offset(&self, options: &WriteOptions) -> Result<u64> {
+        match self.textures_ptr {
+            // the texture headers can't overlap the count and the pointer itself
+            Some(ptr) if (ptr as u64) < RAW_HEADER_SIZE => Err(TextureError::InvalidTexturesPtr(ptr)),
+            Some(ptr) => Ok(ptr as u64),
+            None => Ok(PACKAGE_HEADER_SIZE as u64 + options.header_reserve as u64),
+        }
+    }
+
+    /// Data offsets of the textures and the total size of the package
+    fn layout(&self, options: &WriteOptions) -> Result<(Vec<u64>, u64)> {
+        let align = options.align.max(1) as u64;
+        let mut offset = self.textures_offset(options)? + self.textures.len() as u64 * TEX_HEADER_SIZE as u64;
+        let mut offsets = Vec::with_capacity(self.textures.len());
+        for texture in self.textures.iter() {
+            offset = offset.div_ceil(align) * align;
+            offsets.push(offset);
+            offset += texture.encoded_size_with_row_align(options.row_align)?;
+        }
+        Ok((offsets, offset))
+    }
+
+    /// Loads the textures of a directory along with their paths, in file name order
+    fn load_directory(path: &Path, manifest: Option<&[ManifestEntry]>, options: &DirectoryOptions) -> Result<Vec<(PathBuf, Texture)>> {
         let mut textures = Vec::new();
 
-        let items = std::fs::read_dir(path)?;
-        for item in items {
-            let item = item?;
-            let path = item.path();
-            if path.is_dir() {
-                continue;
+        let root = path;
+        for path in texture_files(root, options.recursive)? {
+            let file_name = relative_name(root, &path);
+            let manifest_entry = manifest
+                .and_then(|m| m.iter().find(|e| Some(&e.filename) == file_name.as_ref()));
+            let meta = match manifest_entry {
+                Some(entry) => Ok(entry.meta),
+                None => sidecar_meta(&path),
+            };
+            let texture = meta.and_then(|meta| {
+                let format = options.force_format.unwrap_or(meta.texture_format);
+                Texture::load_image_file(&path, meta, format)
+            }).and_then(|mut texture| {
+                let stem = path.file_stem().and_then(|v| v.to_str()).unwrap_or_default();
+                let id = options.names.id(stem)
+                    .or_else(|| options.name_template.as_ref().and_then(|template| template.id(stem)))
+                    .or_else(|| options.id_from_name.map(|hash| hash(stem)));
+                if let Some(id) = id {
+                    texture.meta.id = id;
+                }
+                if let Some(resize) = options.resize {
+                    texture.resize(&resize)?;
+                }
+                texture.check_dimensions(options)?;
+                Ok(texture)
+            }).map_err(|err| TextureError::Load {
+                path: path.clone(),
+                source: Box::new(err),
+            })?;
+            textures.push((path, texture));
+        }
+
+        for entry in manifest.unwrap_or_default() {
+            if !textures.iter().any(|(p, _)| relative_name(root, p).as_ref() == Some(&entry.filename)) {
+                return Err(TextureError::MissingManifestFile(path.join(&entry.filename)));
             }
-            if path.extension().and_then(|v| v.to_str()) != Some("png") {
-                continue;
+        }
+        Ok(textures)
+    }
+
+    /// Checks the ids of loaded textures and sorts them, see [`TexturePackage::from_directory`]
+    fn from_loaded(mut textures: Vec<(PathBuf, Texture)>, options: &DirectoryOptions) -> Result<Self> {
+        if options.sequential_ids {
+            for (id, (_, texture)) in textures.iter_mut().enumerate() {
+                texture.meta.id = id as u32;
             }
-            let meta_path = path.with_extension("json");
-            if !meta_path.exists() {
-                bail!("Missing meta file for texture {}", path.display());
+        }
+
+        if !options.allow_duplicate_ids {
+            let mut by_id: Vec<_> = textures.iter().collect();
+            by_id.sort_by_key(|(_, t)| t.meta.id);
+            let duplicates: Vec<_> = by_id.windows(2)
+                .filter(|w| w[0].1.meta.id == w[1].1.meta.id)
+                .map(|w| format!("{:08x} ({} and {})", w[0].1.meta.id, w[0].0.display(), w[1].0.display()))
+                .collect();
+            if !duplicates.is_empty() {
+                return Err(TextureError::DuplicateIds(duplicates.join(", ")));
             }
-            let meta: TextureMeta = serde_json::from_slice(&std::fs::read(meta_path)?)?;
-            let data = image::open(path)?.into_rgba8();
-            let texture = Texture {
-                meta,
-                data,
-            };
-            textures.push(texture);
         }
 
+        textures.sort_by_key(|(_, t)| (t.meta.order.is_none(), t.meta.order, t.meta.id));
+
         Ok(Self {
+            textures_ptr: None,
+            textures: textures.into_iter().map(|(_, t)| t).collect(),
+        })
+    }
+}
+
+/// Builds a package from images, without going through files
+///
+/// Created with [`TexturePackage::builder`]. The unknown header fields of textures added with
+/// [`TexturePackageBuilder::add_texture`] are zero, [`TexturePackageBuilder::add_texture_with_meta`]
+/// takes the whole meta, usually with `..TextureMeta::default()` for the fields that don't matter.
+#[derive(Debug, Default)]
+pub struct TexturePackageBuilder {
+    textures: Vec<(TextureMeta, RgbaImage)>,
+}
+
+impl TexturePackageBuilder {
+    /// Adds a texture with the other header fields taken from [`TextureMeta::default`]
+    pub fn add_texture(self, id: u32, image: RgbaImage, texture_format: TextureFormat) -> Self {
+        self.add_texture_with_meta(TextureMeta { id, texture_format, ..TextureMeta::default() }, image)
+    }
+
+    /// Adds a texture with the given meta, the textures keep the order they were added in
+    pub fn add_texture_with_meta(mut self, meta: TextureMeta, image: RgbaImage) -> Self {
+        self.textures.push((meta, image));
+        self
+    }
+
+    /// Generates the mip levels requested by the meta of each texture and returns the package
+    pub fn build(self) -> Result<TexturePackage> {
+        let textures = self.textures.into_iter()
+            .map(|(meta, data)| {
+                let mips = crate::mip::generate_mips_with_gamma(&data, meta.mip_count, meta.mip_gamma)?;
+                Ok(Texture { meta, data, mips, raw: None })
+            })
+            .collect::<Result<_>>()?;
+        Ok(TexturePackage {
+            textures_ptr: None,
             textures,
         })
     }
 }
 
-#[derive(BinWrite, Debug)]
+/// Lists the `.png` and `.ktx2` files of a texture directory, in the order their textures are loaded in
+pub fn texture_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut paths = if recursive {
+        walkdir::WalkDir::new(dir)
+            .sort_by_file_name()
+            .into_iter()
+            .map(|item| item.map(|item| item.into_path()))
+            .collect::<walkdir::Result<Vec<_>>>()
+            .map_err(std::io::Error::from)?
+    } else {
+        std::fs::read_dir(dir)?
+            .map(|item| item.map(|item| item.path()))
+            .collect::<std::io::Result<Vec<_>>>()?
+    };
+    paths.sort();
+    paths.retain(|path| !path.is_dir() && matches!(path.extension().and_then(|v| v.to_str()), Some("png" | "ktx2")));
+    Ok(paths)
+}
+
+/// Path of a file relative to the texture directory, with `/` separators as in manifests
+fn relative_name(root: &Path, path: &Path) -> Option<String> {
+    let components = path.strip_prefix(root).ok()?
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(components.join("/"))
+}
+
+/// Meta of a single texture in a manifest file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManifestEntry {
+    /// Name of the image file, relative to the texture directory (like `ui/button.png` when loading recursively)
+    pub filename: String,
+    /// The meta can be in the plain or the pretty form, see [`crate::meta`]
+    #[serde(flatten, deserialize_with = "crate::meta::deserialize_meta")]
+    pub meta: TextureMeta,
+}
+
+/// Reads a manifest: a JSON array holding the meta of all the textures in a directory
+pub fn read_manifest(path: &Path) -> Result<Vec<ManifestEntry>> {
+    serde_json::from_slice(&std::fs::read(path)?).map_err(|source| TextureError::InvalidMeta {
+        path: path.to_path_buf(),
+        source: source.into(),
+    })
+}
+
+/// Texture headers of a package, read without decoding any of the pixel data
+#[binread]
+#[derive(Debug)]
+pub struct TexturePackageHeaders {
+    #[br(temp)]
+    texture_count: u32,
+    /// Offset of the first texture header
+    #[br(restore_position)]
+    pub textures_ptr: u32,
+    #[br(parse_with = FilePtr32::parse, count = texture_count)]
+    pub entries: Vec<TextureEntry>,
+}
+
+impl TexturePackageHeaders {
+    /// Number of levels, counting the base one, that fit into the data of each texture
+    ///
+    /// The headers don't record mip levels, so this counts the complete levels that fit before the
+    /// data of the next texture (or the end of the file). Padding between the textures may make it
+    /// count levels that aren't there.
+    pub fn stored_level_counts(&self, file_size: u64) -> Result<Vec<u32>> {
+        let starts = data_starts(&self.entries);
+        self.entries.iter().map(|entry| {
+            check_data_offset(entry.header.id, entry.data_offset, file_size)?;
+            let next = next_start(&starts, entry, file_size as usize);
+            Ok(stored_levels(&entry.header, entry.data_offset as usize, next)?.1)
+        }).collect()
+    }
+}
+
+/// Sorted data offsets of the textures, without duplicates
+fn data_starts(entries: &[TextureEntry]) -> Vec<usize> {
+    let mut starts: Vec<_> = entries.iter().map(|e| e.data_offset as usize).collect();
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
+
+/// Start of the data following that of a texture, or the end of the file
+fn next_start(starts: &[usize], entry: &TextureEntry, file_size: usize) -> usize {
+    starts.iter().copied().find(|&offset| offset > entry.data_offset as usize).unwrap_or(file_size)
+}
+
+/// Size of the complete levels of a texture that fit between `start` and `next`, and their number
+fn stored_levels(header: &TextureHeader, start: usize, next: usize) -> Result<(usize, u32)> {
+    let (mut width, mut height) = (header.width, header.height);
+    let mut extent = header.data_size()? as usize;
+    let mut count = 1;
+    for _ in 1..crate::mip::max_mip_count(width, height) {
+        (width, height) = ((width / 2).max(1), (height / 2).max(1));
+        let level = data_size(header.texture_format, width, height)? as usize;
+        if start + extent + level > next {
+            break;
+        }
+        extent += level;
+        count += 1;
+    }
+    Ok((extent, count))
+}
+
+#[derive(BinRead, Debug)]
+pub struct TextureEntry {
+    pub header: TextureHeader,
+    pub data_offset: u32,
+}
+
+impl TextureEntry {
+    /// Returns the raw pixel data of the texture from the package file contents
+    pub fn raw_data<'a>(&self, package: &'a [u8]) -> Result<&'a [u8]> {
+        self.raw_data_with_row_align(package, 0)
+    }
+
+    /// Same as [`TextureEntry::raw_data`], for packages whose rows start at multiples of `row_align` bytes
+    ///
+    /// The data keeps the row padding, decode it with [`TextureHeader::data_args_with_row_align`].
+    pub fn raw_data_with_row_align<'a>(&self, package: &'a [u8], row_align: u32) -> Result<&'a [u8]> {
+        check_data_offset(self.header.id, self.data_offset, package.len() as u64)?;
+        let start = self.data_offset as usize;
+        let size = self.header.data_size_with_row_align(row_align)? as usize;
+        match package.get(start..start + size) {
+            Some(data) => Ok(data),
+            None => Err(TextureError::TruncatedData {
+                expected: size as u64,
+                actual: package.len().saturating_sub(start) as u64,
+            }),
+        }
+    }
+}
+
+#[derive(BinRead, BinWrite, Debug)]
 pub struct TexturePackageHeaderRaw {
     pub texture_count: u32,
     pub textures_ptr: u32,
 }
 
-#[derive(BinRead, BinWrite, Copy, Clone, Debug, Serialize, Deserialize, clap::ValueEnum)]
+#[derive(BinRead, BinWrite, Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 #[brw(repr = u32)]
 pub enum TextureFormat {
     R5G5B5A1 = 0,
     R4G4B4A4 = 1,
     R5G6B5 = 2,
     R8G8B8A8 = 3,
+    /// 8-bit indices into a palette of 256 RGBA8 colors stored before them, see [`crate::palette`]
+    ///
+    /// The game's packages haven't been seen using it, so the format value is a guess.
+    P8 = 4,
+    /// 8-bit luminance, decoded as gray and fully opaque
+    L8 = 5,
+    /// 8-bit alpha, decoded as white with that alpha
+    A8 = 6,
+}
+
+impl TextureFormat {
+    /// All the formats, in the order of their header values
+    pub const ALL: [TextureFormat; 7] = [
+        TextureFormat::R5G5B5A1,
+        TextureFormat::R4G4B4A4,
+        TextureFormat::R5G6B5,
+        TextureFormat::R8G8B8A8,
+        TextureFormat::P8,
+        TextureFormat::L8,
+        TextureFormat::A8,
+    ];
+
+    /// Returns the format with the given value of the header field, if it is a known one
+    pub fn from_raw(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(TextureFormat::R5G5B5A1),
+            1 => Some(TextureFormat::R4G4B4A4),
+            2 => Some(TextureFormat::R5G6B5),
+            3 => Some(TextureFormat::R8G8B8A8),
+            4 => Some(TextureFormat::P8),
+            5 => Some(TextureFormat::L8),
+            6 => Some(TextureFormat::A8),
+            _ => None,
+        }
+    }
+
+    /// Lowercase name of the format, as accepted by `--format`
+    pub fn name(self) -> &'static str {
+        match self {
+            TextureFormat::R5G5B5A1 => "r5g5b5a1",
+            TextureFormat::R4G4B4A4 => "r4g4b4a4",
+            TextureFormat::R5G6B5 => "r5g6b5",
+            TextureFormat::R8G8B8A8 => "r8g8b8a8",
+            TextureFormat::P8 => "p8",
+            TextureFormat::L8 => "l8",
+            TextureFormat::A8 => "a8",
+        }
+    }
+
+    /// Whether the pixels are stored as 16-bit values, which are byte-swapped in big-endian packages
+    pub fn is_16_bit(self) -> bool {
+        matches!(self, TextureFormat::R5G5B5A1 | TextureFormat::R4G4B4A4 | TextureFormat::R5G6B5)
+    }
 }
 
 #[derive(BinRead, BinWrite, Debug)]
@@ -72,9 +476,25 @@ pub struct TextureHeader {
     pub unk_10: i32,
     pub unk_14: i32,
     pub unk_18: i32,
+    #[br(parse_with = read_texture_format, args(id))]
     pub texture_format: TextureFormat,
 }
 
+/// Reads the format field of a texture header, reporting unknown values with the texture they belong to
+fn read_texture_format<R: Read + Seek>(reader: &mut R, options: &BinReadOptions, (id,): (u32,)) -> BinResult<TextureFormat> {
+    let pos = reader.stream_position()?;
+    let value = u32::read_options(reader, options, ())?;
+    TextureFormat::from_raw(value).ok_or_else(|| binrw::Error::Custom {
+        pos,
+        err: Box::new(TextureError::UnknownFormat {
+            id,
+            // the format is the last of the 8 fields of the header
+            offset: pos - 7 * 4,
+            format: value,
+        }),
+    })
+}
+
 impl TextureHeader {
     pub fn meta(&self) -> TextureMeta {
         TextureMeta {
@@ -84,12 +504,44 @@ impl TextureHeader {
             unk_14: self.unk_14,
             unk_18: self.unk_18,
             texture_format: self.texture_format,
+            order: None,
+            mip_count: default_mip_count(),
+            mip_gamma: MipGamma::Srgb,
+            image_hash: None,
+            no_flip: false,
+            premultiplied: false,
+            channel_order: ChannelOrder::Rgba,
         }
     }
 
-    pub fn data_size(&self) -> u32 {
+    pub fn data_size(&self) -> Result<u32> {
         self.meta().data_size(self.width, self.height)
     }
+
+    /// Size of the pixel data in a package whose rows start at multiples of `row_align` bytes
+    pub fn data_size_with_row_align(&self, row_align: u32) -> Result<u32> {
+        let stride = aligned_row_stride(self.texture_format, self.width, row_align)?;
+        strided_data_size(self.texture_format, self.width, self.height, stride)
+    }
+
+    pub fn data_args(&self) -> TextureDataArgs {
+        TextureDataArgs {
+            width: self.width,
+            height: self.height,
+            texture_format: self.texture_format,
+            no_flip: false,
+            channel_order: ChannelOrder::Rgba,
+            row_stride: None,
+        }
+    }
+
+    /// Same as [`TextureHeader::data_args`], with the stride of rows starting at multiples of `row_align` bytes
+    pub fn data_args_with_row_align(&self, row_align: u32) -> Result<TextureDataArgs> {
+        Ok(TextureDataArgs {
+            row_stride: aligned_row_stride(self.texture_format, self.width, row_align)?,
+            ..self.data_args()
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
@@ -100,174 +552,1449 @@ pub struct TextureMeta {
     pub unk_14: i32,
     pub unk_18: i32,
     pub texture_format: TextureFormat,
+    /// Index of the texture in the package it was extracted from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    /// Number of mip levels, including the base one
+    #[serde(default = "default_mip_count", skip_serializing_if = "is_default_mip_count")]
+    pub mip_count: u32,
+    /// Color space the mip levels are generated in
+    #[serde(default, skip_serializing_if = "MipGamma::is_srgb")]
+    pub mip_gamma: MipGamma,
+    /// [`image_hash`] of the image when it was extracted along with its raw data
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "crate::meta::deserialize_image_hash")]
+    pub image_hash: Option<u64>,
+    /// The rows are stored top-down, so the image is not flipped when converting it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_flip: bool,
+    /// The image was saved with premultiplied alpha, so it has to be unpremultiplied before encoding
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub premultiplied: bool,
+    /// Order the color channels are stored in
+    #[serde(default, skip_serializing_if = "ChannelOrder::is_rgba")]
+    pub channel_order: ChannelOrder,
+}
+
+/// Order of the color channels in the texture data
+///
+/// Some variants of the assets store the red and blue channels swapped, in every format. In the
+/// 16-bit formats the red and blue fields have the same width, so only their positions change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelOrder {
+    #[default]
+    Rgba,
+    Bgra,
+}
+
+impl ChannelOrder {
+    pub fn is_rgba(&self) -> bool {
+        *self == ChannelOrder::Rgba
+    }
+}
+
+pub(crate) fn default_mip_count() -> u32 {
+    1
 }
 
-pub fn data_size(format: TextureFormat, width: u32, height: u32) -> u32 {
-    let bpp = match format {
-        TextureFormat::R5G5B5A1 => 2,
-        TextureFormat::R4G4B4A4 => 2,
-        TextureFormat::R5G6B5 => 2,
-        TextureFormat::R8G8B8A8 => 4,
+pub(crate) fn is_default_mip_count(count: &u32) -> bool {
+    *count == default_mip_count()
+}
+
+/// Computes the size of the pixel data of a texture
+///
+/// Fails if the size does not fit into the 32-bit offsets used by the package format.
+pub fn data_size(format: TextureFormat, width: u32, height: u32) -> Result<u32> {
+    let (bpp, extra) = pixel_layout(format);
+    // the product of two u32s always fits into a u64, but with the bytes per pixel it may not
+    (width as u64 * height as u64).checked_mul(bpp)
+        .and_then(|size| size.checked_add(extra))
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or(TextureError::SizeOverflow {
+            width,
+            height,
+            format,
+        })
+}
+
+/// Computes the size of the pixel data of a texture whose rows start `row_stride` bytes apart
+///
+/// `None` means tightly packed rows, as in [`data_size`]. Every row is padded to the stride,
+/// including the last one. Fails if the stride is smaller than a row.
+pub fn strided_data_size(format: TextureFormat, width: u32, height: u32, row_stride: Option<u32>) -> Result<u32> {
+    let stride = match row_stride {
+        Some(stride) => stride as u64,
+        None => return data_size(format, width, height),
     };
-    width * height * bpp
+    let (bpp, extra) = pixel_layout(format);
+    let row_size = width as u64 * bpp;
+    if stride < row_size {
+        return Err(TextureError::InvalidRowStride { stride: stride as u32, row_size });
+    }
+    u32::try_from(stride * height as u64 + extra).map_err(|_| TextureError::SizeOverflow {
+        width,
+        height,
+        format,
+    })
+}
+
+/// Row stride of a texture whose rows start at multiples of `row_align` bytes
+///
+/// `None` for tightly packed rows, when `row_align` is 0 or 1. Fails if the stride does not fit
+/// into 32 bits.
+pub fn aligned_row_stride(format: TextureFormat, width: u32, row_align: u32) -> Result<Option<u32>> {
+    if row_align <= 1 {
+        return Ok(None);
+    }
+    let row_size = width as u64 * pixel_layout(format).0;
+    let stride = row_size.div_ceil(row_align as u64) * row_align as u64;
+    match u32::try_from(stride) {
+        Ok(stride) => Ok(Some(stride)),
+        Err(_) => Err(TextureError::SizeOverflow { width, height: 1, format }),
+    }
+}
+
+/// Bytes per pixel of a format, and the size of the data before the pixels (the palette of P8)
+fn pixel_layout(format: TextureFormat) -> (u64, u64) {
+    match format {
+        TextureFormat::R5G5B5A1 => (2, 0),
+        TextureFormat::R4G4B4A4 => (2, 0),
+        TextureFormat::R5G6B5 => (2, 0),
+        TextureFormat::R8G8B8A8 => (4, 0),
+        TextureFormat::P8 => (1, crate::palette::PALETTE_SIZE as u64),
+        TextureFormat::L8 | TextureFormat::A8 => (1, 0),
+    }
+}
+
+/// Copies pixel data to rows `to_stride` bytes apart, from rows `from_stride` bytes apart
+///
+/// The strides have been checked to fit the rows, and the data to fit all of them.
+fn restride(data: &[u8], args: &TextureDataArgs, from_stride: usize, to_stride: usize) -> Vec<u8> {
+    let (bpp, extra) = pixel_layout(args.texture_format);
+    let (bpp, extra) = (bpp as usize, extra as usize);
+    let row_size = args.width as usize * bpp;
+    let mut result = Vec::with_capacity(extra + to_stride * args.height as usize);
+    result.extend_from_slice(&data[..extra]);
+    for y in 0..args.height as usize {
+        let start = extra + y * from_stride;
+        result.extend_from_slice(&data[start..start + row_size]);
+        // zero padding up to the next row
+        result.resize(extra + (y + 1) * to_stride, 0);
+    }
+    result
+}
+
+/// Wraps decoded RGBA8 pixels into an image, failing instead of panicking if there are too few or too many
+pub(crate) fn image_from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Result<RgbaImage> {
+    let expected = width as u64 * height as u64 * 4;
+    let actual = pixels.len() as u64;
+    RgbaImage::from_vec(width, height, pixels).ok_or(TextureError::PixelBufferSize { width, height, expected, actual })
+}
+
+/// Meta of an R8G8B8A8 texture with id 0, a single mip level and all the unknown fields zeroed
+impl Default for TextureMeta {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            unk_c: 0,
+            unk_10: 0,
+            unk_14: 0,
+            unk_18: 0,
+            texture_format: TextureFormat::R8G8B8A8,
+            order: None,
+            mip_count: default_mip_count(),
+            mip_gamma: MipGamma::Srgb,
+            image_hash: None,
+            no_flip: false,
+            premultiplied: false,
+            channel_order: ChannelOrder::Rgba,
+        }
+    }
 }
 
 impl TextureMeta {
-    pub fn data_size(&self, width: u32, height: u32) -> u32 {
+    pub fn data_size(&self, width: u32, height: u32) -> Result<u32> {
         data_size(self.texture_format, width, height)
     }
+
+    /// Horizontal wrap mode, believed to be stored in `unk_c`
+    pub fn wrap_u(&self) -> WrapMode {
+        WrapMode::from_raw(self.unk_c)
+    }
+
+    pub fn set_wrap_u(&mut self, mode: WrapMode) {
+        self.unk_c = mode.to_raw();
+    }
+
+    /// Vertical wrap mode, believed to be stored in `unk_10`
+    pub fn wrap_v(&self) -> WrapMode {
+        WrapMode::from_raw(self.unk_10)
+    }
+
+    pub fn set_wrap_v(&mut self, mode: WrapMode) {
+        self.unk_10 = mode.to_raw();
+    }
+
+    /// Flags bitfield, believed to be stored in `unk_18`
+    pub fn flags(&self) -> TextureFlags {
+        TextureFlags(self.unk_18 as u32)
+    }
+
+    pub fn set_flags(&mut self, flags: TextureFlags) {
+        self.unk_18 = flags.0 as i32;
+    }
+}
+
+/// How texture coordinates outside of the texture are handled
+///
+/// The meaning of the values is a guess, the raw value is kept for the ones that aren't known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+    Unknown(i32),
+}
+
+impl WrapMode {
+    pub fn from_raw(value: i32) -> Self {
+        match value {
+            0 => WrapMode::Repeat,
+            1 => WrapMode::Clamp,
+            2 => WrapMode::Mirror,
+            value => WrapMode::Unknown(value),
+        }
+    }
+
+    pub fn to_raw(self) -> i32 {
+        match self {
+            WrapMode::Repeat => 0,
+            WrapMode::Clamp => 1,
+            WrapMode::Mirror => 2,
+            WrapMode::Unknown(value) => value,
+        }
+    }
+}
+
+/// Flags of a texture, none of the bits are known yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextureFlags(pub u32);
+
+impl TextureFlags {
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Checks whether the bit with the given index is set
+    pub fn is_set(self, bit: u32) -> bool {
+        bit < 32 && self.0 & (1 << bit) != 0
+    }
 }
 
 #[binread]
 #[derive(Debug)]
+#[cfg_attr(feature = "package-serde", derive(Serialize, Deserialize))]
 pub struct Texture {
     #[br(temp)]
     pub header: TextureHeader,
     #[br(map = |_: ()| header.meta())]
     pub meta: TextureMeta,
-    #[br(parse_with = &FilePtr32::parse_with(read_texture_data), args { width: header.width, height: header.height, texture_format: header.texture_format })]
+    #[br(parse_with = &read_texture_data_ptr(header.id), args { width: header.width, height: header.height, texture_format: header.texture_format })]
+    #[cfg_attr(feature = "package-serde", serde(with = "crate::package_serde::png"))]
     pub data: RgbaImage,
+    /// Mip levels below the base one, each half the size of the previous one
+    ///
+    /// Only written: the headers don't record mip levels, so reading a package leaves this empty
+    /// and drops any stored after the base level, see [`TexturePackageHeaders::stored_level_counts`].
+    #[br(default)]
+    #[cfg_attr(feature = "package-serde", serde(with = "crate::package_serde::png_vec"))]
+    pub mips: Vec<RgbaImage>,
+    /// Original packed data of the base level, written instead of re-encoding `data`
+    #[br(default)]
+    pub raw: Option<RawTextureData>,
+}
+
+/// Packed pixel data of a texture, little-endian as stored in a package
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "package-serde", derive(Serialize, Deserialize))]
+pub struct RawTextureData {
+    pub texture_format: TextureFormat,
+    #[cfg_attr(feature = "package-serde", serde(with = "crate::package_serde::bytes"))]
+    pub data: Vec<u8>,
+}
+
+/// Hashes the dimensions and pixels of an image (64-bit FNV-1a)
+///
+/// Used to detect whether an extracted image was edited, so the hash has to be stable across builds.
+pub fn image_hash(image: &RgbaImage) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let (width, height) = image.dimensions();
+    let bytes = width.to_le_bytes().into_iter()
+        .chain(height.to_le_bytes())
+        .chain(image.as_raw().iter().copied());
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Converts packed pixel data stored in the given byte order to little-endian
+pub fn to_little_endian(data: &[u8], format: TextureFormat, endian: Endian) -> Vec<u8> {
+    let mut data = data.to_vec();
+    if endian == Endian::Big && format.is_16_bit() {
+        for pixel in data.chunks_exact_mut(2) {
+            pixel.swap(0, 1);
+        }
+    }
+    data
+}
+
+/// Reads the meta of an image file from its sidecar, see [`crate::meta::find_meta`]
+fn sidecar_meta(image: &Path) -> Result<TextureMeta> {
+    let (meta_path, format) = crate::meta::find_meta(image)?;
+    crate::meta::read_meta(&std::fs::read(&meta_path)?, format)
+        .map_err(|source| TextureError::InvalidMeta {
+            path: meta_path,
+            source,
+        })
+}
+
+impl Texture {
+    /// Loads a texture from a `.png` or `.ktx2` file and its `.json` or `.toml` sidecar
+    pub fn from_files(path: &Path) -> Result<Self> {
+        Self::from_image_file(path, sidecar_meta(path)?)
+    }
+
+    /// Loads a texture from an image file, with the given meta
+    ///
+    /// If the meta has an image hash matching the image and a `.bin` file with the raw data
+    /// is next to it, the raw data is kept to be written back as is. The same goes for the
+    /// data of `.ktx2` files, which is already stored packed.
+    pub fn from_image_file(path: &Path, meta: TextureMeta) -> Result<Self> {
+        Self::load_image_file(path, meta, meta.texture_format)
+    }
+
+    /// Same as [`Texture::from_image_file`], but the texture gets stored in `format`
+    ///
+    /// The raw data from a `.bin` file is still in the format of the meta, so it is only used if
+    /// the formats are the same.
+    fn load_image_file(path: &Path, mut meta: TextureMeta, format: TextureFormat) -> Result<Self> {
+        let original_format = std::mem::replace(&mut meta.texture_format, format);
+        if path.extension().and_then(|v| v.to_str()) == Some("ktx2") {
+            let (args, raw) = crate::ktx2::read_ktx2(&std::fs::read(path)?, meta.no_flip)?;
+            let data = decode_texture(&raw, &args)?;
+            let mips = crate::mip::generate_mips_with_gamma(&data, meta.mip_count, meta.mip_gamma)?;
+            return Ok(Texture {
+                meta,
+                data,
+                mips,
+                raw: Some(RawTextureData {
+                    texture_format: args.texture_format,
+                    data: raw,
+                }),
+            });
+        }
+        let data = from_dynamic_image(image::open(path)?, format);
+        let mips = crate::mip::generate_mips_with_gamma(&data, meta.mip_count, meta.mip_gamma)?;
+        let raw_path = path.with_extension("bin");
+        let raw = match meta.image_hash {
+            Some(hash) if format == original_format && hash == image_hash(&data) && raw_path.exists() => Some(RawTextureData {
+                texture_format: original_format,
+                data: std::fs::read(&raw_path)?,
+            }),
+            _ => None,
+        };
+        Ok(Texture {
+            meta,
+            data,
+            mips,
+            raw,
+        })
+    }
+
+    /// Decodes a texture from the bytes of a PNG file, with the given meta
+    ///
+    /// Unlike [`Texture::from_image_file`] there is no raw data to keep, so the image is always re-encoded.
+    pub fn from_png_bytes(meta: TextureMeta, bytes: &[u8]) -> Result<Self> {
+        let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)?;
+        let data = from_dynamic_image(image, meta.texture_format);
+        let mips = crate::mip::generate_mips_with_gamma(&data, meta.mip_count, meta.mip_gamma)?;
+        Ok(Texture {
+            meta,
+            data,
+            mips,
+            raw: None,
+        })
+    }
+
+    /// Encodes the base level of the texture as a PNG file, see [`encode_png`]
+    pub fn to_png_bytes(&self) -> Result<Vec<u8>> {
+        encode_png(&self.data)
+    }
+
+    /// Scales the texture to new dimensions, regenerating the mip levels
+    ///
+    /// Any raw data is dropped, as it no longer matches the image. Textures that already have the
+    /// requested dimensions are left as is.
+    pub fn resize(&mut self, resize: &Resize) -> Result<()> {
+        if self.data.dimensions() == (resize.width, resize.height) {
+            return Ok(());
+        }
+        if self.data.width() == 0 || self.data.height() == 0 || resize.width == 0 || resize.height == 0 {
+            return Err(TextureError::EmptyTexture(self.meta.id));
+        }
+        self.data = image::imageops::resize(&self.data, resize.width, resize.height, resize.filter);
+        self.mips = crate::mip::generate_mips_with_gamma(&self.data, self.meta.mip_count, self.meta.mip_gamma)?;
+        self.raw = None;
+        Ok(())
+    }
+
+    /// Fails if the texture is empty, or its dimensions are not allowed by the options
+    pub fn check_dimensions(&self, options: &DirectoryOptions) -> Result<()> {
+        let (width, height) = self.data.dimensions();
+        if width == 0 || height == 0 {
+            return Err(TextureError::EmptyTexture(self.meta.id));
+        }
+        if let Some(max) = options.max_dimension {
+            if width > max || height > max {
+                return Err(TextureError::TextureTooLarge {
+                    id: self.meta.id,
+                    width,
+                    height,
+                    max,
+                });
+            }
+        }
+        if options.require_pow2 && !self.is_pow2() {
+            return Err(TextureError::NotPowerOfTwo {
+                id: self.meta.id,
+                width,
+                height,
+            });
+        }
+        Ok(())
+    }
+
+    /// Converts the base level and the mip levels from premultiplied to straight alpha
+    ///
+    /// Clears [`TextureMeta::premultiplied`], as the texture data is always stored with straight alpha.
+    pub fn unpremultiply(&mut self) {
+        unpremultiply_alpha(&mut self.data);
+        for mip in self.mips.iter_mut() {
+            unpremultiply_alpha(mip);
+        }
+        self.meta.premultiplied = false;
+    }
+
+    /// Checks whether both the width and the height are powers of two, which GPUs may require
+    pub fn is_pow2(&self) -> bool {
+        self.data.width().is_power_of_two() && self.data.height().is_power_of_two()
+    }
+
+    /// Header describing the texture in a package, without the data offset
+    fn header(&self) -> TextureHeader {
+        TextureHeader {
+            id: self.meta.id,
+            width: self.data.width(),
+            height: self.data.height(),
+            texture_format: self.meta.texture_format,
+            unk_c: self.meta.unk_c,
+            unk_10: self.meta.unk_10,
+            unk_14: self.meta.unk_14,
+            unk_18: self.meta.unk_18,
+        }
+    }
+
+    /// Size of the data [`Texture::encode`] produces, including the mip levels
+    pub fn encoded_size(&self) -> Result<u64> {
+        self.encoded_size_with_row_align(0)
+    }
+
+    /// Size of the data [`Texture::encode_with_row_align`] produces, including the mip levels
+    pub fn encoded_size_with_row_align(&self, row_align: u32) -> Result<u64> {
+        let format = self.meta.texture_format;
+        let level_size = |(width, height): (u32, u32)| {
+            strided_data_size(format, width, height, aligned_row_stride(format, width, row_align)?)
+        };
+        let mut size = level_size(self.data.dimensions())? as u64;
+        for mip in self.mips.iter() {
+            size += level_size(mip.dimensions())? as u64;
+        }
+        Ok(size)
+    }
+
+    /// Encodes the pixel data of the texture, with the mip levels following the base one
+    ///
+    /// Whether the rows are flipped and the channel order are taken from the meta of the texture.
+    pub fn encode(&self, options: &EncodeOptions) -> Result<Vec<u8>> {
+        self.encode_with_row_align(options, 0)
+    }
+
+    /// Same as [`Texture::encode`], with the rows of every level padded to multiples of `row_align` bytes
+    pub fn encode_with_row_align(&self, options: &EncodeOptions, row_align: u32) -> Result<Vec<u8>> {
+        let format = self.meta.texture_format;
+        let level_options = |width: u32| -> Result<EncodeOptions> {
+            Ok(EncodeOptions {
+                no_flip: self.meta.no_flip,
+                channel_order: self.meta.channel_order,
+                row_stride: aligned_row_stride(format, width, row_align)?,
+                ..*options
+            })
+        };
+        let (width, height) = self.data.dimensions();
+        let base_options = level_options(width)?;
+        let size = data_size(format, width, height)? as usize;
+        let mut data = match &self.raw {
+            // the raw data is only usable if the format wasn't changed since
+            Some(raw) if raw.texture_format == format && raw.data.len() == size => match base_options.row_stride {
+                Some(stride) => {
+                    let args = TextureDataArgs { width, height, texture_format: format, ..self.header().data_args() };
+                    let row_size = (width as u64 * pixel_layout(format).0) as usize;
+                    restride(&raw.data, &args, row_size, stride as usize)
+                }
+                None => raw.data.clone(),
+            },
+            _ => encode_texture_with_options(&self.data, format, &base_options)?,
+        };
+        let (mut width, mut height) = self.data.dimensions();
+        for mip in self.mips.iter() {
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            if mip.dimensions() != (width, height) {
+                return Err(TextureError::DimensionMismatch {
+                    expected: (width, height),
+                    actual: mip.dimensions(),
+                });
+            }
+            data.extend(encode_texture_with_options(mip, format, &level_options(width)?)?);
+        }
+        Ok(data)
+    }
 }
 
 #[derive(BinrwNamedArgs, Clone, Debug)]
 pub struct TextureDataArgs {
-    width: u32,
-    height: u32,
-    texture_format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub texture_format: TextureFormat,
+    /// Don't flip the image, for textures stored top-down
+    #[named_args(default = false)]
+    pub no_flip: bool,
+    /// Swap the red and blue channels of the decoded image, for textures stored as BGRA
+    #[named_args(default = ChannelOrder::Rgba)]
+    pub channel_order: ChannelOrder,
+    /// Distance between the starts of the rows in bytes, for layouts that pad every row
+    ///
+    /// `None` means tightly packed rows. In P8 textures the stride is that of the index rows
+    /// following the palette.
+    #[named_args(default = None)]
+    pub row_stride: Option<u32>,
+}
+
+/// Decodes the raw pixel data of a single texture, as stored in a package
+pub fn decode_texture(data: &[u8], args: &TextureDataArgs) -> Result<RgbaImage> {
+    decode_texture_with_endian(data, args, Endian::Little)
 }
 
-fn read_texture_data<R: Read + Seek>(reader: &mut R, _options: &ReadOptions, args: TextureDataArgs) -> BinResult<RgbaImage> {
+/// Decodes the raw pixel data of a single texture, with the 16-bit pixels stored in the given byte order
+pub fn decode_texture_with_endian(data: &[u8], args: &TextureDataArgs, endian: Endian) -> Result<RgbaImage> {
     let format = args.texture_format;
 
-    let size = data_size(format, args.width, args.height) as usize;
-    let mut data = vec![0; size];
-    reader.read_exact(&mut data)?;
+    if let Some(stride) = args.row_stride {
+        let size = strided_data_size(format, args.width, args.height, Some(stride))? as usize;
+        if data.len() != size {
+            return Err(TextureError::TruncatedData {
+                expected: size as u64,
+                actual: data.len() as u64,
+            });
+        }
+        let tight_args = TextureDataArgs {
+            row_stride: None,
+            ..args.clone()
+        };
+        let row_size = args.width as usize * pixel_layout(format).0 as usize;
+        let tight = restride(data, &tight_args, stride as usize, row_size);
+        return decode_texture_with_endian(&tight, &tight_args, endian);
+    }
+
+    let size = data_size(format, args.width, args.height)? as usize;
+    if data.len() != size {
+        return Err(TextureError::TruncatedData {
+            expected: size as u64,
+            actual: data.len() as u64,
+        });
+    }
 
-    let image: RgbaImage = match format {
+    // the textures seem to be stored upside-down because OpenGL
+    let mut flip = !args.no_flip;
+    let mut image: RgbaImage = match format {
         TextureFormat::R5G5B5A1 |TextureFormat::R4G4B4A4 |TextureFormat::R5G6B5 => {
-            let shorts: &[u16] = bytemuck::cast_slice(&data);
+            let read_short = match endian {
+                Endian::Big => u16::from_be_bytes,
+                Endian::Little => u16::from_le_bytes,
+            };
+            let expand: fn(u16) -> [u8; 4] = match format {
+                TextureFormat::R5G5B5A1 => |short| [
+                    dequantize(short >> 11, 5),
+                    dequantize(short >> 6, 5),
+                    dequantize(short >> 1, 5),
+                    dequantize(short, 1),
+                ],
+                TextureFormat::R4G4B4A4 => |short| [
+                    dequantize(short >> 12, 4),
+                    dequantize(short >> 8, 4),
+                    dequantize(short >> 4, 4),
+                    dequantize(short, 4),
+                ],
+                TextureFormat::R5G6B5 => |short| [
+                    dequantize(short >> 11, 5),
+                    dequantize(short >> 5, 6),
+                    dequantize(short, 5),
+                    0xFF,
+                ],
+                _ => unreachable!(),
+            };
+
+            // the length check above guarantees there is no remainder
+            let mut pixels = vec![0; data.len() * 2];
+            for (pixel, short) in pixels.chunks_exact_mut(4).zip(data.chunks_exact(2)) {
+                pixel.copy_from_slice(&expand(read_short([short[0], short[1]])));
+            }
+            image_from_pixels(args.width, args.height, pixels)?
+        }
+        TextureFormat::R8G8B8A8 => {
+            // already in the layout of the image, so the rows can be flipped while copying them
+            let row_size = args.width as usize * 4;
+            let pixels = if flip && row_size != 0 {
+                flip = false;
+                let mut pixels = Vec::with_capacity(data.len());
+                for row in data.chunks_exact(row_size).rev() {
+                    pixels.extend_from_slice(row);
+                }
+                pixels
+            } else {
+                data.to_vec()
+            };
+            image_from_pixels(args.width, args.height, pixels)?
+        }
+        TextureFormat::P8 => crate::palette::decode_p8(data, args.width, args.height)?,
+        TextureFormat::L8 => {
+            let pixels = data.iter().flat_map(|&l| [l, l, l, 0xFF]).collect();
+            image_from_pixels(args.width, args.height, pixels)?
+        }
+        TextureFormat::A8 => {
+            let pixels = data.iter().flat_map(|&a| [0xFF, 0xFF, 0xFF, a]).collect();
+            image_from_pixels(args.width, args.height, pixels)?
+        }
+    };
+
+    if flip {
+        image::imageops::flip_vertical_in_place(&mut image);
+    }
+    if args.channel_order == ChannelOrder::Bgra {
+        swap_red_blue(&mut image);
+    }
+
+    Ok(image)
+}
+
+/// Encodes an image as a PNG file tagged as sRGB, which the texture data is assumed to be
+///
+/// Without the tag editors disagree on whether the colors are linear or not.
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+    Ok(buf)
+}
+
+/// Encodes an image as a 16-bit PNG file, with the channels of the texture format scaled to the full range
+///
+/// The packed value of every channel is recovered from the image, which has to be decoded from a
+/// texture of that format, and its bits are repeated to fill 16 bits. Channels the format doesn't
+/// store are only widened. [`from_dynamic_image`] turns such an image back into the exact packed values.
+pub fn encode_png_16(image: &RgbaImage, format: TextureFormat) -> Result<Vec<u8>> {
+    let bits = channel_bits(format);
+    let mut samples = Vec::with_capacity(image.as_raw().len() * 2);
+    for pixel in image.pixels() {
+        for c in 0..4 {
+            let value = match bits[c] {
+                0 => pixel[c] as u16 * 0x101,
+                bits => dequantize_16(quantize(pixel[c], bits), bits),
+            };
+            // PNG samples are big-endian
+            samples.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&samples)?;
+    writer.finish()?;
+    Ok(buf)
+}
+
+/// Converts a loaded image to 8 bits per channel for a texture of the given format
+///
+/// Images with 16-bit channels are reduced straight to the bits of the format, so that the packed
+/// values written by [`encode_png_16`] are recovered exactly. Other images are converted as usual.
+pub fn from_dynamic_image(image: image::DynamicImage, format: TextureFormat) -> RgbaImage {
+    let color = image.color();
+    if color.bytes_per_pixel() / color.channel_count() < 2 {
+        return image.into_rgba8();
+    }
+    let bits = channel_bits(format);
+    let wide = image.into_rgba16();
+    let mut result = RgbaImage::new(wide.width(), wide.height());
+    for (pixel, wide_pixel) in result.pixels_mut().zip(wide.pixels()) {
+        for c in 0..4 {
+            // channels the format doesn't store are reduced to 8 bits like the rest
+            let bits = if bits[c] == 0 { 8 } else { bits[c] };
+            let max = (1u32 << bits) - 1;
+            let value = (wide_pixel[c] as u32 * max + 0x7FFF) / 0xFFFF;
+            pixel[c] = dequantize(value as u16, bits);
+        }
+    }
+    result
+}
+
+/// Swaps the red and blue channels of every pixel, converting between RGBA and BGRA
+pub fn swap_red_blue(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        pixel.0.swap(0, 2);
+    }
+}
 
+/// Multiplies the color channels of every pixel by its alpha
+pub fn premultiply_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        for c in pixel.0[..3].iter_mut() {
+            *c = ((*c as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Divides the color channels of every pixel by its alpha, the inverse of [`premultiply_alpha`]
+///
+/// Fully transparent pixels have no color left to recover and are kept black.
+pub fn unpremultiply_alpha(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            continue;
+        }
+        for c in pixel.0[..3].iter_mut() {
+            *c = ((*c as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// Encodes an image as a lossless WebP file, which is usually much smaller than the PNG one
+#[cfg(feature = "webp")]
+pub fn encode_webp(image: &RgbaImage) -> Result<Vec<u8>> {
+    use image::codecs::webp::{WebPEncoder, WebPQuality};
+
+    let mut buf = Vec::new();
+    WebPEncoder::new_with_quality(&mut buf, WebPQuality::lossless())
+        .encode(image.as_raw(), image.width(), image.height(), image::ColorType::Rgba8)?;
+    Ok(buf)
+}
+
+/// Options controlling how images are converted to the packed texture formats
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodeOptions {
+    /// Diffuse the quantization error of the color channels (Floyd–Steinberg) to avoid banding
+    pub dither: bool,
+    /// Store the rows top-down instead of flipping the image
+    pub no_flip: bool,
+    /// Store the red and blue channels swapped
+    pub channel_order: ChannelOrder,
+    /// Pad every row with zeros to this many bytes, see [`TextureDataArgs::row_stride`]
+    ///
+    /// Ignored by [`Texture::encode`], which packs the rows tightly, see
+    /// [`WriteOptions::row_align`] for packages with padded rows.
+    pub row_stride: Option<u32>,
+}
+
+/// Number of bits stored for each of the R, G, B and A channels in the given format
+pub fn channel_bits(format: TextureFormat) -> [u32; 4] {
+    match format {
+        TextureFormat::R5G5B5A1 => [5, 5, 5, 1],
+        TextureFormat::R4G4B4A4 => [4, 4, 4, 4],
+        TextureFormat::R5G6B5 => [5, 6, 5, 0],
+        TextureFormat::R8G8B8A8 | TextureFormat::P8 => [8, 8, 8, 8],
+        TextureFormat::L8 => [8, 8, 8, 0],
+        TextureFormat::A8 => [0, 0, 0, 8],
+    }
+}
+
+/// Luminance of an sRGB color with the Rec. 601 weights, as used for the L8 format
+pub fn luminance([r, g, b]: [u8; 3]) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+}
+
+/// Reduces an 8-bit channel to `bits` bits, rounding to the nearest level
+///
+/// This is the inverse of [`dequantize`]: quantizing a dequantized level gives the same level back,
+/// so converting a texture repeatedly doesn't drift.
+pub fn quantize(value: u8, bits: u32) -> u16 {
+    match bits {
+        0 => 0,
+        _ => ((value as u32 * ((1 << bits) - 1) + 0x7F) / 0xFF) as u16,
+    }
+}
+
+/// Expands the low `bits` bits of `value` to a full 8-bit channel by bit replication,
+/// so that 0 maps to 0 and the maximum value maps to 255
+///
+/// Channels that aren't stored at all (`bits` is 0) are fully on, which makes textures without an
+/// alpha channel opaque.
+pub fn dequantize(value: u16, bits: u32) -> u8 {
+    if bits == 0 {
+        return 0xFF;
+    }
+    let value = (value & ((1 << bits) - 1)) as u32;
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < 8 {
+        result = (result << bits) | value;
+        filled += bits;
+    }
+    (result >> (filled - 8)) as u8
+}
+
+/// Same as [`dequantize`], filling 16 bits
+fn dequantize_16(value: u16, bits: u32) -> u16 {
+    let value = (value & ((1 << bits) - 1)) as u32;
+    let mut result = 0u32;
+    let mut filled = 0;
+    while filled < 16 {
+        result = (result << bits) | value;
+        filled += bits;
+    }
+    (result >> (filled - 16)) as u16
+}
+
+/// Reduces the channels of every pixel to the given number of bits, in row-major order
+fn quantize_image(image: &RgbaImage, bits: [u32; 4], options: &EncodeOptions) -> Vec<[u16; 4]> {
+    if !options.dither {
+        return image.pixels()
+            .map(|pix| [0, 1, 2, 3].map(|c| quantize(pix[c], bits[c])))
+            .collect();
+    }
+
+    let width = image.width() as usize;
+    // quantization error carried into the current and the next row, for the color channels
+    let mut errors = vec![[0f32; 3]; width + 2];
+    let mut next_errors = vec![[0f32; 3]; width + 2];
+    let mut result = Vec::with_capacity(width * image.height() as usize);
+
+    for row in image.rows() {
+        for (x, pix) in row.enumerate() {
+            let mut levels = [0, 1, 2, 3].map(|c| quantize(pix[c], bits[c]));
+            for c in 0..3 {
+                if bits[c] == 0 || bits[c] >= 8 {
+                    continue;
+                }
+                let max = ((1 << bits[c]) - 1) as f32;
+                let wanted = pix[c] as f32 + errors[x + 1][c];
+                let level = (wanted * max / 255.0).round().clamp(0.0, max) as u16;
+                let error = wanted - dequantize(level, bits[c]) as f32;
+                levels[c] = level;
+
+                errors[x + 2][c] += error * 7.0 / 16.0;
+                next_errors[x][c] += error * 3.0 / 16.0;
+                next_errors[x + 1][c] += error * 5.0 / 16.0;
+                next_errors[x + 2][c] += error / 16.0;
+            }
+            result.push(levels);
+        }
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.fill([0.0; 3]);
+    }
+
+    result
+}
+
+/// Encodes an image into the raw pixel data of a texture in the given format
+pub fn encode_texture(image: &RgbaImage, format: TextureFormat) -> Result<Vec<u8>> {
+    encode_texture_with_options(image, format, &EncodeOptions::default())
+}
+
+pub fn encode_texture_with_options(image: &RgbaImage, format: TextureFormat, options: &EncodeOptions) -> Result<Vec<u8>> {
+    if options.channel_order == ChannelOrder::Bgra {
+        let mut swapped = image.clone();
+        swap_red_blue(&mut swapped);
+        let options = EncodeOptions {
+            channel_order: ChannelOrder::Rgba,
+            ..*options
+        };
+        return encode_texture_with_options(&swapped, format, &options);
+    }
+    if let Some(stride) = options.row_stride {
+        strided_data_size(format, image.width(), image.height(), Some(stride))?;
+        let options = EncodeOptions {
+            row_stride: None,
+            ..*options
+        };
+        let tight = encode_texture_with_options(image, format, &options)?;
+        let args = TextureDataArgs {
+            width: image.width(),
+            height: image.height(),
+            texture_format: format,
+            no_flip: options.no_flip,
+            channel_order: ChannelOrder::Rgba,
+            row_stride: None,
+        };
+        let row_size = image.width() as usize * pixel_layout(format).0 as usize;
+        return Ok(restride(&tight, &args, row_size, stride as usize));
+    }
+    let size = data_size(format, image.width(), image.height())? as usize;
+    if format == TextureFormat::P8 {
+        return Ok(crate::palette::encode_p8(image, options.no_flip));
+    }
+    let mut data = Vec::with_capacity(size);
+
+    let levels = quantize_image(image, channel_bits(format), options);
+    let mut rows: Vec<_> = levels.chunks(image.width().max(1) as usize).collect();
+    // the textures are stored upside-down, see `decode_texture`
+    if !options.no_flip {
+        rows.reverse();
+    }
+    for row in rows {
+        for &[r, g, b, a] in row {
             match format {
-                TextureFormat::R5G5B5A1 | TextureFormat::R4G4B4A4 => {
-                    let mut pixels = Vec::new();
-                    for &short in shorts.iter() {
-                        match format {
-                            TextureFormat::R5G5B5A1 => {
-                                pixels.push( (((short >> 11) & 0x1F) * 0xFF / 0x1F) as u8);
-                                pixels.push( (((short >> 6) & 0x1F) * 0xFF / 0x1F) as u8);
-                                pixels.push( (((short >> 1) & 0x1F) * 0xFF / 0x1F) as u8);
-                                pixels.push( (((short >> 0) & 0x1) * 0xFF) as u8);
-                            }
-                            TextureFormat::R4G4B4A4 => {
-                                pixels.push((((short >> 12) & 0xF) * 0xFF / 0xF) as u8);
-                                pixels.push((((short >> 8) & 0xF) * 0xFF / 0xF) as u8);
-                                pixels.push((((short >> 4) & 0xF) * 0xFF / 0xF) as u8);
-                                pixels.push((((short >> 0) & 0xF) * 0xFF / 0xF) as u8);
-                            }
-                            _ => unreachable!(),
-                        }
-                    }
-                    RgbaImage::from_vec(args.width, args.height, pixels).unwrap()
+                TextureFormat::R5G5B5A1 => {
+                    let short = r << 11 | g << 6 | b << 1 | a;
+                    data.extend_from_slice(&short.to_le_bytes());
+                }
+                TextureFormat::R4G4B4A4 => {
+                    let short = r << 12 | g << 8 | b << 4 | a;
+                    data.extend_from_slice(&short.to_le_bytes());
+                }
+                TextureFormat::R8G8B8A8 => {
+                    data.extend_from_slice(&[r as u8, g as u8, b as u8, a as u8]);
                 }
                 TextureFormat::R5G6B5 => {
-                    let mut pixels = Vec::new();
-                    for &short in shorts.iter() {
-                        pixels.push((((short >> 11) & 0x1F) * 0xFF / 0x1F) as u8);
-                        pixels.push((((short >> 5) & 0x3F) * 0xFF / 0x3F) as u8);
-                        pixels.push((((short >> 0) & 0x1F) * 0xFF / 0x1F) as u8);
-                    }
-                    DynamicImage::from(RgbImage::from_vec(args.width, args.height, pixels).unwrap()).into_rgba8()
+                    let short = r << 11 | g << 5 | b;
+                    data.extend_from_slice(&short.to_le_bytes());
                 }
-                _ => unreachable!(),
+                TextureFormat::L8 => data.push(luminance([r as u8, g as u8, b as u8])),
+                TextureFormat::A8 => data.push(a as u8),
+                TextureFormat::P8 => unreachable!("palettized textures are encoded above"),
             }
         }
-        TextureFormat::R8G8B8A8 => {
-            RgbaImage::from_vec(args.width, args.height, data).unwrap()
-        }
+    }
+
+    assert_eq!(data.len(), size);
+
+    Ok(data)
+}
+
+fn read_texture_data<R: Read + Seek>(reader: &mut R, options: &BinReadOptions, args: TextureDataArgs) -> BinResult<RgbaImage> {
+    let pos = reader.stream_position()?;
+    let custom_err = |err: TextureError| binrw::Error::Custom {
+        pos,
+        err: Box::new(err),
     };
 
-    // the textures seem to be stored upside-down because OpenGL
-    let image = DynamicImage::from(image).flipv().into_rgba8();
+    let size = strided_data_size(args.texture_format, args.width, args.height, args.row_stride)
+        .map_err(custom_err)? as usize;
+    let available = reader.seek(SeekFrom::End(0))?.saturating_sub(pos);
+    reader.seek(SeekFrom::Start(pos))?;
+    if available < size as u64 {
+        return Err(custom_err(TextureError::TruncatedData {
+            expected: size as u64,
+            actual: available,
+        }));
+    }
+    tracing::debug!(offset = pos, format = ?args.texture_format, width = args.width, height = args.height, size, "reading texture data");
+    let mut data = vec![0; size];
+    reader.read_exact(&mut data)?;
 
+    let image = decode_texture_with_endian(&data, &args, options.endian()).map_err(custom_err)?;
+    tracing::trace!(decoded_size = image.as_raw().len(), "decoded texture data");
     Ok(image)
 }
 
+/// Follows the data pointer of a texture header and reads the data, in a span naming the texture
+fn read_texture_data_ptr<R: Read + Seek>(id: u32) -> impl Fn(&mut R, &BinReadOptions, TextureDataArgs) -> BinResult<RgbaImage> {
+    move |reader, options, args| {
+        let _span = tracing::debug_span!("texture", id = %format_args!("{:08x}", id)).entered();
+        let pos = reader.stream_position()?;
+        let offset = u32::read_options(reader, options, ())?;
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        check_data_offset(id, offset, file_size).map_err(|err| binrw::Error::Custom {
+            pos,
+            err: Box::new(err),
+        })?;
+        // like `FilePtr32`, but with the offset checked first
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        let image = read_texture_data(reader, options, args)?;
+        reader.seek(SeekFrom::Start(pos + 4))?;
+        Ok(image)
+    }
+}
+
+/// Checks that the data of a texture starts inside the file, instead of failing on a read past its end
+fn check_data_offset(id: u32, offset: u32, file_size: u64) -> Result<()> {
+    if offset == 0 || offset as u64 > file_size {
+        return Err(TextureError::InvalidDataOffset { id, offset, file_size });
+    }
+    Ok(())
+}
+
+/// Options controlling how [`read_texture_package_with_options`] reads a package
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOptions {
+    /// Byte order of the package, see [`read_texture_package_with_endian`]
+    pub endian: Endian,
+    /// The rows of the texture data start at multiples of this many bytes, the rest being padding
+    ///
+    /// A console variant pads every row to 128 bytes. 0 and 1 mean tightly packed rows.
+    pub row_align: u32,
+}
+
+/// Little-endian with tightly packed rows, as in the iOS version
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            endian: Endian::Little,
+            row_align: 0,
+        }
+    }
+}
+
 pub fn read_texture_package(data: &[u8]) -> Result<TexturePackage> {
-    Ok(binrw::io::Cursor::new(data).read_le()?)
+    read_texture_package_with_endian(data, Endian::Little)
+}
+
+pub fn read_texture_package_with_options(data: &[u8], options: &ReadOptions) -> Result<TexturePackage> {
+    read_texture_package_from_with_options(binrw::io::Cursor::new(data), options)
+}
+
+/// Reads a texture package stored in the given byte order
+///
+/// The iOS version uses little-endian packages, while console versions use big-endian ones.
+pub fn read_texture_package_with_endian(data: &[u8], endian: Endian) -> Result<TexturePackage> {
+    read_texture_package_from_with_endian(binrw::io::Cursor::new(data), endian)
+}
+
+/// Reads a texture package from a stream, without loading the whole file into memory
+pub fn read_texture_package_from<R: Read + Seek>(reader: R) -> Result<TexturePackage> {
+    read_texture_package_from_with_endian(reader, Endian::Little)
+}
+
+pub fn read_texture_package_from_with_endian<R: Read + Seek>(mut reader: R, endian: Endian) -> Result<TexturePackage> {
+    let _span = tracing::debug_span!("read_texture_package", ?endian).entered();
+    let package: TexturePackage = reader.read_type(endian)?;
+    tracing::debug!(textures = package.textures.len(), "read texture package");
+    Ok(package)
 }
 
+/// Reads a package from a stream, with the given byte order and row alignment
+pub fn read_texture_package_from_with_options<R: Read + Seek>(mut reader: R, options: &ReadOptions) -> Result<TexturePackage> {
+    if options.row_align <= 1 {
+        return read_texture_package_from_with_endian(reader, options.endian);
+    }
+    let _span = tracing::debug_span!("read_texture_package", endian = ?options.endian, row_align = options.row_align).entered();
+    let headers = read_texture_headers_from_with_endian(&mut reader, options.endian)?;
+    let textures = headers.entries.iter()
+        .map(|entry| load_texture_with_options(&mut reader, entry, options))
+        .collect::<Result<Vec<_>>>()?;
+    tracing::debug!(textures = textures.len(), "read texture package");
+    Ok(TexturePackage {
+        textures_ptr: Some(headers.textures_ptr),
+        textures,
+    })
+}
+
+pub fn read_texture_headers(data: &[u8]) -> Result<TexturePackageHeaders> {
+    read_texture_headers_with_endian(data, Endian::Little)
+}
+
+pub fn read_texture_headers_with_endian(data: &[u8], endian: Endian) -> Result<TexturePackageHeaders> {
+    read_texture_headers_from_with_endian(binrw::io::Cursor::new(data), endian)
+}
+
+/// Reads the texture headers of a package from a stream, reading only the headers themselves
+pub fn read_texture_headers_from<R: Read + Seek>(reader: R) -> Result<TexturePackageHeaders> {
+    read_texture_headers_from_with_endian(reader, Endian::Little)
+}
+
+pub fn read_texture_headers_from_with_endian<R: Read + Seek>(mut reader: R, endian: Endian) -> Result<TexturePackageHeaders> {
+    Ok(reader.read_type(endian)?)
+}
+
+/// Decodes a single texture of a package on demand, given its entry from [`read_texture_headers_from`]
+pub fn load_texture<R: Read + Seek>(reader: &mut R, entry: &TextureEntry) -> Result<Texture> {
+    load_texture_with_endian(reader, entry, Endian::Little)
+}
+
+pub fn load_texture_with_endian<R: Read + Seek>(reader: &mut R, entry: &TextureEntry, endian: Endian) -> Result<Texture> {
+    load_texture_with_options(reader, entry, &ReadOptions { endian, ..ReadOptions::default() })
+}
+
+/// Decodes a single texture of a package on demand, with the given byte order and row alignment
+pub fn load_texture_with_options<R: Read + Seek>(reader: &mut R, entry: &TextureEntry, options: &ReadOptions) -> Result<Texture> {
+    let _span = tracing::debug_span!("texture", id = %format_args!("{:08x}", entry.header.id)).entered();
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    check_data_offset(entry.header.id, entry.data_offset, file_size)?;
+    reader.seek(SeekFrom::Start(entry.data_offset as u64))?;
+    let args = entry.header.data_args_with_row_align(options.row_align)?;
+    let data = read_texture_data(reader, &BinReadOptions::new(options.endian), args)?;
+    Ok(Texture {
+        meta: entry.header.meta(),
+        data,
+        mips: Vec::new(),
+        raw: None,
+    })
+}
+
+/// Size of the package header written by [`write_texture_package`], the texture headers follow it
+pub const PACKAGE_HEADER_SIZE: u32 = 0x20;
+/// Size of the texture count and the `textures_ptr` at the start of a package
+const RAW_HEADER_SIZE: u64 = 2 * 4;
+/// Size of a texture header in the package: the eight 32-bit fields of [`TextureHeader`] and the data offset
+pub const TEX_HEADER_SIZE: u32 = 9 * 4;
+
+/// Options controlling how [`write_texture_package_with_options`] lays out a package
+#[derive(Clone, Default)]
+pub struct WriteOptions {
+    pub encode: EncodeOptions,
+    /// Start the data of every texture at a multiple of this many bytes, padding with zeros in between
+    ///
+    /// Some loaders need the data aligned for DMA transfers. 0 and 1 mean no alignment.
+    pub align: u32,
+    /// Zero bytes to reserve after the package header, before the texture headers
+    ///
+    /// Some variants of the format have a larger header, the texture headers are found through the
+    /// `textures_ptr` field anyway. Ignored for packages with [`TexturePackage::textures_ptr`] set.
+    pub header_reserve: u32,
+    /// Pad the rows of every texture and mip level with zeros to multiples of this many bytes
+    ///
+    /// See [`ReadOptions::row_align`] for reading such packages back. 0 and 1 mean tightly packed rows.
+    pub row_align: u32,
+    /// Called once for every encoded texture, to show the progress of large packages
+    ///
+    /// With the `rayon` feature it is called from the worker threads.
+    pub progress: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl Debug for WriteOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WriteOptions")
+            .field("encode", &self.encode)
+            .field("align", &self.align)
+            .field("header_reserve", &self.header_reserve)
+            .field("row_align", &self.row_align)
+            .field("progress", &self.progress.as_ref().map(|_| "Fn()"))
+            .finish()
+    }
+}
+
+/// Serializes a package, the output only depends on the textures and the options
+///
+/// Writing the same package always gives the same bytes, also with the `rayon` feature and on
+/// other platforms, and the gaps in the file are filled with zeros. Generated packages can be
+/// compared byte for byte and stored in version control.
 pub fn write_texture_package(data: &TexturePackage) -> Result<Vec<u8>> {
+    write_texture_package_with_options(data, &WriteOptions::default())
+}
+
+pub fn write_texture_package_with_options(data: &TexturePackage, options: &WriteOptions) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
+    // an empty texture would have its data offset point at the next texture's data
+    if let Some(texture) = data.textures.iter().find(|t| t.data.width() == 0 || t.data.height() == 0) {
+        return Err(TextureError::EmptyTexture(texture.meta.id));
+    }
+    // fail before spending the time on encoding the textures
+    if data.encoded_size_with_options(options)? > u32::MAX as u64 {
+        return Err(TextureError::PackageTooLarge);
+    }
+    let _span = tracing::debug_span!("write_texture_package", textures = data.textures.len()).entered();
+    let data_offsets = data.data_offsets_with_options(options)?;
+    let textures_offset = data.textures_offset(options)?;
     let header = TexturePackageHeaderRaw {
         texture_count: data.textures.len() as u32,
-        textures_ptr: 0x20,
+        // the total size was checked above
+        textures_ptr: textures_offset as u32,
+    };
+
+    let encode = |texture: &Texture| {
+        let _span = tracing::debug_span!("texture", id = %format_args!("{:08x}", texture.meta.id)).entered();
+        let encoded = texture.encode_with_row_align(&options.encode, options.row_align);
+        if let Some(progress) = &options.progress {
+            progress();
+        }
+        encoded
     };
-    const TEX_HEADER_SIZE: u32 = 36;
-    let mut data_offset = 0x20 + data.textures.len() as u32 * TEX_HEADER_SIZE;
+    #[cfg(feature = "rayon")]
+    let encoded = data.textures.par_iter().map(encode).collect::<Result<Vec<_>>>()?;
+    #[cfg(not(feature = "rayon"))]
+    let encoded = data.textures.iter().map(encode).collect::<Result<Vec<_>>>()?;
 
     let mut cur = std::io::Cursor::new(&mut buf);
     header.write_le(&mut cur)?;
-    cur.seek(SeekFrom::Start(0x20))?;
-    for texture in &data.textures {
-        let data_size = texture.meta.data_size(texture.data.width(), texture.data.height());
-        let header = TextureHeader {
-            id: texture.meta.id,
-            width: texture.data.width(),
-            height: texture.data.height(),
-            texture_format: texture.meta.texture_format,
-            unk_c: texture.meta.unk_c,
-            unk_10: texture.meta.unk_10,
-            unk_14: texture.meta.unk_14,
-            unk_18: texture.meta.unk_18,
-        };
+    // zeros up to the headers, which also keeps `textures_ptr` inside the file for packages without textures
+    cur.get_mut().resize(textures_offset as usize, 0);
+    cur.seek(SeekFrom::Start(textures_offset))?;
+    for (texture, &data_offset) in data.textures.iter().zip(data_offsets.iter()) {
+        texture.header().write_le(&mut cur)?;
+        // the total size was checked above
+        (data_offset as u32).write_le(&mut cur)?;
+    }
 
-        header.write_le(&mut cur)?;
-        data_offset.write_le(&mut cur)?;
+    assert_eq!(cur.position(), textures_offset + data.textures.len() as u64 * TEX_HEADER_SIZE as u64);
+
+    for ((texture, texture_data), data_offset) in data.textures.iter().zip(encoded).zip(data_offsets) {
+        tracing::debug!(id = %format_args!("{:08x}", texture.meta.id), offset = data_offset, size = texture_data.len(), "writing texture data");
+        // zero padding up to the aligned offset
+        cur.get_mut().resize(data_offset as usize, 0);
+        cur.set_position(data_offset);
+        cur.write_all(&texture_data)?;
+    }
+
+    Ok(buf)
+}
+
+/// Replaces the pixel data of the textures with the given id, keeping the rest of the package as is
+///
+/// The image is encoded in the existing format of the texture and written over the old data. If its
+/// size changed, the data of the following textures is moved and their offsets are updated.
+pub fn replace_texture(package: &[u8], id: u32, image: &RgbaImage, options: &EncodeOptions) -> Result<Vec<u8>> {
+    let raw_header: TexturePackageHeaderRaw = binrw::io::Cursor::new(package).read_le()?;
+    let indices: Vec<_> = read_texture_headers(package)?.entries.iter()
+        .enumerate()
+        .filter(|(_, e)| e.header.id == id)
+        .map(|(index, _)| index)
+        .collect();
+    if indices.is_empty() {
+        return Err(TextureError::TextureNotFound(id));
+    }
+    if image.width() == 0 || image.height() == 0 {
+        return Err(TextureError::EmptyTexture(id));
+    }
+
+    let mut result = package.to_vec();
+    for index in indices {
+        // re-read, the offsets change with every replaced texture
+        let entries = read_texture_headers(&result)?.entries;
+        let entry = &entries[index];
+        let start = entry.data_offset as usize;
+        let old_size = entry.raw_data(&result)?.len();
+        let data = encode_texture_with_options(image, entry.header.texture_format, options)?;
+
+        result.splice(start..start + old_size, data.iter().copied());
+        u32::try_from(result.len()).map_err(|_| TextureError::PackageTooLarge)?;
 
-        data_offset += data_size;
-    }
-
-    assert_eq!(cur.position(), 0x20 + data.textures.len() as u64 * TEX_HEADER_SIZE as u64);
-
-    for texture in &data.textures {
-        let format = texture.meta.texture_format;
-        let data = &texture.data;
-        for row in data.rows().rev() {
-            for pix in row {
-                match format {
-                    TextureFormat::R5G5B5A1 => {
-                        let r = (pix[0] as u16 * 0x1F / 0xFF) << 11;
-                        let g = (pix[1] as u16 * 0x1F / 0xFF) << 6;
-                        let b = (pix[2] as u16 * 0x1F / 0xFF) << 1;
-                        let a = (pix[3] as u16 * 0x1 / 0xFF) << 0;
-                        let short = r | g | b | a;
-                        short.write_le(&mut cur)?;
-                    }
-                    TextureFormat::R4G4B4A4 => {
-                        let r = (pix[0] as u16 * 0xF / 0xFF) << 12;
-                        let g = (pix[1] as u16 * 0xF / 0xFF) << 8;
-                        let b = (pix[2] as u16 * 0xF / 0xFF) << 4;
-                        let a = (pix[3] as u16 * 0xF / 0xFF) << 0;
-                        let short = r | g | b | a;
-                        short.write_le(&mut cur)?;
-                    }
-                    TextureFormat::R8G8B8A8 => {
-                        pix[0].write_le(&mut cur)?;
-                        pix[1].write_le(&mut cur)?;
-                        pix[2].write_le(&mut cur)?;
-                        pix[3].write_le(&mut cur)?;
-                    }
-                    TextureFormat::R5G6B5 => {
-                        let r = (pix[0] as u16 * 0x1F / 0xFF) << 11;
-                        let g = (pix[1] as u16 * 0x3F / 0xFF) << 5;
-                        let b = (pix[2] as u16 * 0x1F / 0xFF) << 0;
-                        let short = r | g | b;
-                        short.write_le(&mut cur)?;
-                    }
+        let header_start = |index: usize| raw_header.textures_ptr as u64 + index as u64 * TEX_HEADER_SIZE as u64;
+        let mut cur = std::io::Cursor::new(&mut result);
+        cur.seek(SeekFrom::Start(header_start(index) + 4))?;
+        image.width().write_le(&mut cur)?;
+        image.height().write_le(&mut cur)?;
+        if data.len() != old_size {
+            for (other_index, other) in entries.iter().enumerate() {
+                // the `> start` keeps empty textures right at the start of an empty replaced one in place
+                if other.data_offset as usize >= start + old_size && other.data_offset as usize > start {
+                    let offset = other.data_offset as usize - old_size + data.len();
+                    cur.seek(SeekFrom::Start(header_start(other_index) + 32))?;
+                    (offset as u32).write_le(&mut cur)?;
                 }
             }
         }
     }
 
-    Ok(buf)
-}
\ No newline at end of file
+    Ok(result)
+}
+
+/// Reads the texture count and `textures_ptr` of a package, for the functions editing it in place
+fn read_raw_header(package: &[u8]) -> Result<TexturePackageHeaderRaw> {
+    let raw_header: TexturePackageHeaderRaw = binrw::io::Cursor::new(package).read_le()?;
+    // the texture headers can't overlap the count and the pointer itself
+    if (raw_header.textures_ptr as u64) < RAW_HEADER_SIZE {
+        return Err(TextureError::InvalidTexturesPtr(raw_header.textures_ptr));
+    }
+    Ok(raw_header)
+}
+
+/// Adds a texture to the end of a package, keeping the rest of it as is
+///
+/// The header of the new texture is inserted after the existing ones, which moves the data of all
+/// textures by the size of a header, and its data is appended at the end of the file.
+pub fn add_texture(package: &[u8], texture: &Texture, options: &EncodeOptions) -> Result<Vec<u8>> {
+    let raw_header = read_raw_header(package)?;
+    let entries = read_texture_headers(package)?.entries;
+    let id = texture.meta.id;
+    if entries.iter().any(|e| e.header.id == id) {
+        return Err(TextureError::DuplicateIds(format!("{:08x} is already in the package", id)));
+    }
+    if texture.data.width() == 0 || texture.data.height() == 0 {
+        return Err(TextureError::EmptyTexture(id));
+    }
+    let data = texture.encode(options)?;
+
+    let header_start = |index: usize| raw_header.textures_ptr as u64 + index as u64 * TEX_HEADER_SIZE as u64;
+    let headers_end = header_start(entries.len()) as usize;
+    let mut result = package.to_vec();
+    // a package without textures may end before its `textures_ptr`
+    if result.len() < headers_end {
+        result.resize(headers_end, 0);
+    }
+    result.splice(headers_end..headers_end, [0; TEX_HEADER_SIZE as usize]);
+    let data_offset = u32::try_from(result.len()).map_err(|_| TextureError::PackageTooLarge)?;
+    result.extend_from_slice(&data);
+    u32::try_from(result.len()).map_err(|_| TextureError::PackageTooLarge)?;
+
+    let mut cur = std::io::Cursor::new(&mut result);
+    ((entries.len() + 1) as u32).write_le(&mut cur)?;
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.data_offset as usize >= headers_end {
+            cur.seek(SeekFrom::Start(header_start(index) + 32))?;
+            (entry.data_offset + TEX_HEADER_SIZE).write_le(&mut cur)?;
+        }
+    }
+    cur.seek(SeekFrom::Start(headers_end as u64))?;
+    texture.header().write_le(&mut cur)?;
+    data_offset.write_le(&mut cur)?;
+
+    Ok(result)
+}
+
+/// Removes the textures with the given ids from a package, keeping the rest of it as is
+///
+/// The data of a removed texture extends up to the data of the next texture (or the end of the
+/// file), so mip levels are removed along with it. Data shared with a kept texture stays.
+pub fn remove_textures(package: &[u8], ids: &[u32]) -> Result<Vec<u8>> {
+    let raw_header = read_raw_header(package)?;
+    let entries = read_texture_headers(package)?.entries;
+    if let Some(&id) = ids.iter().find(|&&id| !entries.iter().any(|e| e.header.id == id)) {
+        return Err(TextureError::TextureNotFound(id));
+    }
+    // the data ranges are cut between the offsets of all textures
+    for entry in entries.iter() {
+        check_data_offset(entry.header.id, entry.data_offset, package.len() as u64)?;
+    }
+
+    let header_start = |index: usize| raw_header.textures_ptr as usize + index * TEX_HEADER_SIZE as usize;
+    let headers_end = header_start(entries.len());
+    let (removed, kept): (Vec<_>, Vec<_>) = entries.iter()
+        .enumerate()
+        .partition(|(_, e)| ids.contains(&e.header.id));
+
+    let mut dropped: Vec<_> = removed.iter()
+        .map(|&(index, _)| header_start(index)..header_start(index) + TEX_HEADER_SIZE as usize)
+        .collect();
+    for (_, entry) in removed.iter() {
+        let start = entry.data_offset as usize;
+        let end = entries.iter()
+            .map(|e| e.data_offset as usize)
+            .filter(|&offset| offset > start)
+            .min()
+            .unwrap_or(package.len());
+        let shared = kept.iter().any(|(_, e)| (start..end).contains(&(e.data_offset as usize)));
+        if start >= headers_end && !shared {
+            dropped.push(start..end);
+        }
+    }
+    dropped.sort_by_key(|range| range.start);
+    dropped.dedup();
+
+    let mut result = Vec::with_capacity(package.len());
+    let mut position = 0;
+    for range in dropped.iter() {
+        result.extend_from_slice(&package[position..range.start]);
+        position = range.end;
+    }
+    result.extend_from_slice(&package[position..]);
+
+    // offsets move back by the size of everything dropped before them
+    let new_offset = |offset: u32| {
+        let before: usize = dropped.iter().filter(|r| r.end <= offset as usize).map(|r| r.len()).sum();
+        offset - before as u32
+    };
+    let mut cur = std::io::Cursor::new(&mut result);
+    (kept.len() as u32).write_le(&mut cur)?;
+    for (new_index, (_, entry)) in kept.iter().enumerate() {
+        cur.seek(SeekFrom::Start(header_start(new_index) as u64 + 32))?;
+        new_offset(entry.data_offset).write_le(&mut cur)?;
+    }
+
+    Ok(result)
+}
+
+/// Options controlling how [`repack_package`] lays out a package
+#[derive(Debug, Clone, Default)]
+pub struct RepackOptions {
+    /// Start the data of every texture at a multiple of this many bytes, see [`WriteOptions::align`]
+    pub align: u32,
+    /// Keep the textures in the order of the package instead of sorting them by id
+    pub keep_order: bool,
+}
+
+/// Rewrites a package with the textures sorted by id and their data packed tightly, without decoding it
+///
+/// Everything before the texture headers is kept, the data of the textures follows the headers
+/// and is copied as is, so nothing is lost to quantization. The headers don't record mip levels,
+/// so a texture keeps the longest chain of them that fits before the data of the next texture
+/// (or the end of the file), and the rest of the gap is dropped. Data shared by several textures
+/// is stored once.
+pub fn repack_package(package: &[u8], options: &RepackOptions) -> Result<Vec<u8>> {
+    let raw_header = read_raw_header(package)?;
+    let mut entries = read_texture_headers(package)?.entries;
+    if !options.keep_order {
+        // stable, so textures sharing an id keep their order
+        entries.sort_by_key(|e| e.header.id);
+    }
+
+    let starts = data_starts(&entries);
+    let data_extent = |entry: &TextureEntry| -> Result<usize> {
+        // checks that the base level is inside the file
+        entry.raw_data(package)?;
+        let next = next_start(&starts, entry, package.len());
+        Ok(stored_levels(&entry.header, entry.data_offset as usize, next)?.0)
+    };
+
+    let align = options.align.max(1) as usize;
+    let headers_end = raw_header.textures_ptr as usize + entries.len() * TEX_HEADER_SIZE as usize;
+    // packages without textures can end before their `textures_ptr`
+    let mut result = package[..package.len().min(raw_header.textures_ptr as usize)].to_vec();
+    result.resize(headers_end, 0);
+    // new offsets of the data already copied, by their old offset
+    let mut moved: Vec<(u32, u32)> = Vec::new();
+    let mut data_offsets = Vec::with_capacity(entries.len());
+    for entry in entries.iter() {
+        if let Some(&(_, offset)) = moved.iter().find(|(old, _)| *old == entry.data_offset) {
+            data_offsets.push(offset);
+            continue;
+        }
+        let extent = data_extent(entry)?;
+        result.resize(result.len().div_ceil(align) * align, 0);
+        let offset = u32::try_from(result.len()).map_err(|_| TextureError::PackageTooLarge)?;
+        let start = entry.data_offset as usize;
+        result.extend_from_slice(&package[start..start + extent]);
+        u32::try_from(result.len()).map_err(|_| TextureError::PackageTooLarge)?;
+        moved.push((entry.data_offset, offset));
+        data_offsets.push(offset);
+    }
+
+    let mut cur = std::io::Cursor::new(&mut result);
+    cur.seek(SeekFrom::Start(raw_header.textures_ptr as u64))?;
+    for (entry, data_offset) in entries.iter().zip(data_offsets) {
+        entry.header.write_le(&mut cur)?;
+        data_offset.write_le(&mut cur)?;
+    }
+    Ok(result)
+}
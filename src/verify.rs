@@ -0,0 +1,156 @@
+//! Structural checks of texture packages
+
+use std::fmt;
+use std::io::Read;
+use crate::error::Result;
+use crate::texture::{read_texture_headers, read_texture_headers_with_endian, ReadOptions, TexturePackageHeaders, TEX_HEADER_SIZE};
+
+/// A structural problem found in a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    ZeroDimensions { id: u32, width: u32, height: u32 },
+    SizeOverflow { id: u32 },
+    OutOfBounds { id: u32, offset: u64, size: u64, file_size: u64 },
+    OverlapsHeaders { id: u32, offset: u64 },
+    NotIncreasing { id: u32, offset: u64, previous_id: u32, previous_offset: u64 },
+    Overlap { id: u32, previous_id: u32 },
+    DuplicateId { id: u32, first_index: usize, index: usize },
+    /// The [`checksum`] of the package differs from the recorded one
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Problem::ZeroDimensions { id, width, height } => {
+                write!(f, "texture {:08x} has zero dimensions ({}x{})", id, width, height)
+            }
+            Problem::SizeOverflow { id } => {
+                write!(f, "texture {:08x} has a data size that does not fit into 32 bits", id)
+            }
+            Problem::OutOfBounds { id, offset, size, file_size } => write!(
+                f,
+                "texture {:08x} data ({:#x}..{:#x}) is outside of the file ({:#x} bytes)",
+                id, offset, offset + size, file_size
+            ),
+            Problem::OverlapsHeaders { id, offset } => {
+                write!(f, "texture {:08x} data at {:#x} overlaps the texture headers", id, offset)
+            }
+            Problem::NotIncreasing { id, offset, previous_id, previous_offset } => write!(
+                f,
+                "texture {:08x} data at {:#x} comes before the data of the previous texture {:08x} at {:#x}",
+                id, offset, previous_id, previous_offset
+            ),
+            Problem::Overlap { id, previous_id } => {
+                write!(f, "texture {:08x} data overlaps the data of texture {:08x}", id, previous_id)
+            }
+            Problem::DuplicateId { id, first_index, index } => {
+                write!(f, "texture {:08x} at index {} has the same id as the one at index {}", id, index, first_index)
+            }
+            Problem::ChecksumMismatch { expected, actual } => {
+                write!(f, "package checksum is {:08x}, expected {:08x}", actual, expected)
+            }
+        }
+    }
+}
+
+/// Checks the structure of a package, returning all the problems found
+///
+/// Fails only if the headers themselves can't be parsed.
+pub fn verify_package(data: &[u8]) -> Result<Vec<Problem>> {
+    let headers = read_texture_headers(data)?;
+    Ok(verify_headers(&headers, data.len() as u64))
+}
+
+/// Same as [`verify_package`], for packages read with the given options
+pub fn verify_package_with_options(data: &[u8], options: &ReadOptions) -> Result<Vec<Problem>> {
+    let headers = read_texture_headers_with_endian(data, options.endian)?;
+    Ok(verify_headers_with_row_align(&headers, data.len() as u64, options.row_align))
+}
+
+pub fn verify_headers(headers: &TexturePackageHeaders, file_size: u64) -> Vec<Problem> {
+    verify_headers_with_row_align(headers, file_size, 0)
+}
+
+/// Same as [`verify_headers`], for packages whose rows start at multiples of `row_align` bytes
+pub fn verify_headers_with_row_align(headers: &TexturePackageHeaders, file_size: u64, row_align: u32) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let headers_end = headers.textures_ptr as u64 + headers.entries.len() as u64 * TEX_HEADER_SIZE as u64;
+
+    // (id, offset, end) of the previous texture with a valid size
+    let mut previous: Option<(u32, u64, u64)> = None;
+    for (index, entry) in headers.entries.iter().enumerate() {
+        let header = &entry.header;
+        let id = header.id;
+        let offset = entry.data_offset as u64;
+
+        if let Some(first_index) = headers.entries[..index].iter().position(|e| e.header.id == id) {
+            problems.push(Problem::DuplicateId { id, first_index, index });
+        }
+        if header.width == 0 || header.height == 0 {
+            problems.push(Problem::ZeroDimensions { id, width: header.width, height: header.height });
+        }
+        let size = match header.data_size_with_row_align(row_align) {
+            Ok(size) => size as u64,
+            Err(_) => {
+                problems.push(Problem::SizeOverflow { id });
+                continue;
+            }
+        };
+
+        if offset + size > file_size {
+            problems.push(Problem::OutOfBounds { id, offset, size, file_size });
+        }
+        if offset < headers_end {
+            problems.push(Problem::OverlapsHeaders { id, offset });
+        }
+        if let Some((previous_id, previous_offset, previous_end)) = previous {
+            if offset < previous_offset {
+                problems.push(Problem::NotIncreasing { id, offset, previous_id, previous_offset });
+            } else if offset < previous_end {
+                problems.push(Problem::Overlap { id, previous_id });
+            }
+        }
+        previous = Some((id, offset, offset + size));
+    }
+
+    problems
+}
+
+/// Lookup table of the reflected CRC-32 (IEEE) polynomial, one entry per byte value
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-32 of a whole package file, to be stored next to it and detect corrupted copies
+///
+/// The package format has no place for a checksum, so it has to be kept in a separate file.
+pub fn checksum<R: Read>(mut reader: R) -> Result<u32> {
+    let mut crc = !0u32;
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            read => read?,
+        };
+        if read == 0 {
+            break;
+        }
+        for &byte in buf[..read].iter() {
+            crc = CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+    }
+    Ok(!crc)
+}
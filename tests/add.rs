@@ -0,0 +1,54 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::verify::verify_package;
+use smb_tex::{add_texture, read_texture_package, EncodeOptions, TextureError, TextureFormat};
+
+fn texture(id: u32, image: RgbaImage) -> smb_tex::Texture {
+    common::texture(id, TextureFormat::R8G8B8A8, image)
+}
+
+#[test]
+fn added_texture_follows_the_existing_ones() {
+    let package = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    let image = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8, y as u8, 7, 0xFF]));
+
+    let result = add_texture(&package, &texture(0x4321, image.clone()), &EncodeOptions::default()).unwrap();
+    assert_eq!(verify_package(&result).unwrap(), []);
+    let tp = read_texture_package(&result).unwrap();
+    assert_eq!(tp.textures.len(), 2);
+    assert_eq!(tp.textures[0].meta.id, 0x1234);
+    assert_eq!(tp.textures[0].data.as_raw(), &[1; 8]);
+    assert_eq!(tp.textures[1].meta.id, 0x4321);
+    assert_eq!(tp.textures[1].data, image);
+}
+
+#[test]
+fn adding_an_existing_id_fails() {
+    let package = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+
+    let err = add_texture(&package, &texture(0x1234, RgbaImage::new(1, 1)), &EncodeOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::DuplicateIds(_)), "{err}");
+}
+
+#[test]
+fn textures_are_added_to_a_package_ending_before_its_headers() {
+    // no textures, and the headers would start past the end of the file
+    let package = [0u32.to_le_bytes(), 0x20u32.to_le_bytes()].concat();
+    assert!(read_texture_package(&package).unwrap().textures.is_empty());
+    let image = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8, y as u8, 3, 0xFF]));
+
+    let result = add_texture(&package, &texture(0x4321, image.clone()), &EncodeOptions::default()).unwrap();
+    assert_eq!(verify_package(&result).unwrap(), []);
+    let tp = read_texture_package(&result).unwrap();
+    assert_eq!(tp.textures.len(), 1);
+    assert_eq!(tp.textures[0].data, image);
+}
+
+#[test]
+fn headers_inside_the_package_header_are_rejected() {
+    let package = [0u32.to_le_bytes(), 4u32.to_le_bytes()].concat();
+
+    let err = add_texture(&package, &texture(0x4321, RgbaImage::new(1, 1)), &EncodeOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::InvalidTexturesPtr(4)), "{err}");
+}
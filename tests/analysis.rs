@@ -0,0 +1,49 @@
+use image::{Rgba, RgbaImage};
+use smb_tex::analysis::{choose_format, color_stats, precision_loss, ColorStats};
+use smb_tex::TextureFormat;
+
+#[test]
+fn color_stats_count_distinct_colors_and_alpha() {
+    let mut image = RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 0xFF]));
+    assert_eq!(color_stats(&image), ColorStats { translucent: false, distinct_colors: 1 });
+
+    image.put_pixel(0, 0, Rgba([1, 2, 3, 0xFE]));
+    image.put_pixel(1, 0, Rgba([4, 5, 6, 0xFF]));
+    assert_eq!(color_stats(&image), ColorStats { translucent: true, distinct_colors: 3 });
+}
+
+#[test]
+fn format_is_chosen_by_alpha_and_colors() {
+    let opaque = RgbaImage::from_fn(4, 4, |x, y| Rgba([x as u8 * 64, y as u8 * 64, 0, 0xFF]));
+    assert_eq!(choose_format(&opaque), TextureFormat::R5G6B5);
+
+    let cutout = RgbaImage::from_fn(4, 4, |x, _| Rgba([0xFF, 0, 0, if x < 2 { 0 } else { 0xFF }]));
+    assert_eq!(choose_format(&cutout), TextureFormat::R5G5B5A1);
+
+    let faded = RgbaImage::from_fn(4, 4, |x, _| Rgba([0xFF, 0, 0, x as u8 * 85]));
+    assert_eq!(choose_format(&faded), TextureFormat::R4G4B4A4);
+
+    let smooth_alpha = RgbaImage::from_fn(256, 1, |x, _| Rgba([0xFF, 0, 0, x as u8]));
+    assert_eq!(choose_format(&smooth_alpha), TextureFormat::R8G8B8A8);
+
+    // a fine gray ramp, most of its values merge into the same 5-bit levels
+    let gradient = RgbaImage::from_fn(256, 1, |x, _| Rgba([x as u8, x as u8, x as u8, 0xFF]));
+    assert_eq!(choose_format(&gradient), TextureFormat::R8G8B8A8);
+}
+
+#[test]
+fn precision_loss_counts_changed_alpha_and_merged_colors() {
+    // alpha ramp over 16 pixels, 8 of which aren't multiples of 0x11
+    let ramp = RgbaImage::from_fn(16, 1, |x, _| Rgba([0xFF, 0xFF, 0xFF, (x * 0x11) as u8 ^ (x as u8 & 1)]));
+    let loss = precision_loss(&ramp, TextureFormat::R4G4B4A4).unwrap();
+    assert_eq!(loss.alpha_levels, 16);
+    assert_eq!(loss.changed_alpha, 8);
+    assert!(loss.loses_alpha() && !loss.loses_colors());
+    assert!(!precision_loss(&ramp, TextureFormat::R8G8B8A8).unwrap().loses_alpha());
+    assert_eq!(precision_loss(&ramp, TextureFormat::R5G6B5).unwrap().changed_alpha, 16);
+
+    let gradient = RgbaImage::from_fn(256, 1, |x, _| Rgba([x as u8, x as u8, x as u8, 0xFF]));
+    let loss = precision_loss(&gradient, TextureFormat::R4G4B4A4).unwrap();
+    assert_eq!((loss.distinct_colors, loss.stored_colors), (256, 16));
+    assert!(loss.loses_colors() && !loss.loses_alpha());
+}
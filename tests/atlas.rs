@@ -0,0 +1,47 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::atlas::{atlas_package, pack_atlas};
+use smb_tex::{TextureFormat, TexturePackage};
+
+#[test]
+fn packed_images_keep_their_pixels_and_dont_overlap() {
+    let images: Vec<_> = [(5, 3), (1, 7), (4, 4), (9, 1), (2, 2)]
+        .iter()
+        .enumerate()
+        .map(|(i, &(w, h))| RgbaImage::from_fn(w, h, |x, y| Rgba([i as u8, x as u8, y as u8, 0xFF])))
+        .collect();
+    let (atlas, rects) = pack_atlas(&images);
+
+    assert!(atlas.width().is_power_of_two() && atlas.height().is_power_of_two());
+    for (i, (image, rect)) in images.iter().zip(rects.iter()).enumerate() {
+        assert_eq!((rect.width, rect.height), image.dimensions());
+        assert!(rect.x + rect.width <= atlas.width() && rect.y + rect.height <= atlas.height());
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(atlas.get_pixel(rect.x + x, rect.y + y), pixel);
+        }
+        for other in rects[i + 1..].iter() {
+            let apart = rect.x + rect.width <= other.x
+                || other.x + other.width <= rect.x
+                || rect.y + rect.height <= other.y
+                || other.y + other.height <= rect.y;
+            assert!(apart, "{rect:?} overlaps {other:?}");
+        }
+    }
+}
+
+#[test]
+fn mixed_formats_are_packed_as_rgba8() {
+    let texture = |id, format| common::texture(id, format, RgbaImage::new(2, 2));
+    let tp = TexturePackage {
+        textures_ptr: None,
+        textures: vec![texture(1, TextureFormat::R5G6B5), texture(2, TextureFormat::R4G4B4A4)],
+    };
+    let (atlas, entries) = atlas_package(&tp, 0x40);
+
+    assert_eq!(atlas.textures.len(), 1);
+    assert_eq!(atlas.textures[0].meta.id, 0x40);
+    assert_eq!(atlas.textures[0].meta.texture_format, TextureFormat::R8G8B8A8);
+    let ids: Vec<_> = entries.iter().map(|e| e.id).collect();
+    assert_eq!(ids, [1, 2]);
+}
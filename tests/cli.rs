@@ -0,0 +1,72 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+use image::{Rgba, RgbaImage};
+use smb_tex::{decode_texture, read_texture_headers, read_texture_package, write_texture_package, TextureDataArgs, TextureFormat};
+
+fn smb_tex(args: &[&Path]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_smb_tex")).args(args).status().unwrap();
+    assert!(status.success(), "smb_tex {args:?} failed");
+}
+
+#[test]
+fn no_flip_drops_raw_data_in_the_old_row_order() {
+    let dir = common::scratch_dir("no_flip_drops_raw_data_in_the_old_row_order");
+    // rows that aren't symmetric, so that flipping them shows
+    let image = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8 * 100, y as u8 * 200, 7, 0xFF]));
+    let input = dir.join("in.tpg");
+    std::fs::write(&input, write_texture_package(&common::single_texture(image.clone(), TextureFormat::R8G8B8A8)).unwrap()).unwrap();
+
+    let extracted = dir.join("extracted");
+    smb_tex(&[Path::new("extract"), Path::new("--lossless"), &input, &extracted]);
+    let output = dir.join("no_flip.tpg");
+    smb_tex(&[Path::new("create"), Path::new("--no-flip"), &extracted, &output]);
+
+    let data = std::fs::read(&output).unwrap();
+    let entry = &read_texture_headers(&data).unwrap().entries[0];
+    let args = TextureDataArgs {
+        no_flip: true,
+        ..entry.header.data_args()
+    };
+    assert_eq!(decode_texture(entry.raw_data(&data).unwrap(), &args).unwrap(), image);
+}
+
+#[test]
+fn mip_levels_are_not_extracted_but_reported() {
+    let dir = common::scratch_dir("mip_levels_are_not_extracted_but_reported");
+    let package = write_texture_package(&common::package(&[1], TextureFormat::R8G8B8A8, 3)).unwrap();
+    assert!(read_texture_package(&package).unwrap().textures[0].mips.is_empty());
+    assert_eq!(read_texture_headers(&package).unwrap().stored_level_counts(package.len() as u64).unwrap(), [3]);
+    let input = dir.join("in.tpg");
+    std::fs::write(&input, &package).unwrap();
+
+    let extracted = dir.join("extracted");
+    let output = Command::new(env!("CARGO_BIN_EXE_smb_tex")).arg("extract").arg(&input).arg(&extracted).output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("texture 00000001 seems to have 3 levels stored") && stderr.contains("\"mip_count\": 3"), "{stderr}");
+
+    // the mip levels are lost unless the meta asks for them again
+    let recreated = dir.join("recreated.tpg");
+    smb_tex(&[Path::new("create"), &extracted, &recreated]);
+    assert_eq!(std::fs::read(&recreated).unwrap().len(), package.len() - (2 * 2 + 1) * 4);
+    let meta_path = extracted.join("00000001.json");
+    let mut meta: serde_json::Value = serde_json::from_slice(&std::fs::read(&meta_path).unwrap()).unwrap();
+    meta["mip_count"] = 3.into();
+    std::fs::write(&meta_path, meta.to_string()).unwrap();
+    smb_tex(&[Path::new("create"), &extracted, &recreated]);
+    assert_eq!(std::fs::read(&recreated).unwrap(), package);
+}
+
+#[test]
+fn format_names_match_the_command_line_ones() {
+    use clap::ValueEnum;
+
+    assert_eq!(TextureFormat::value_variants(), TextureFormat::ALL);
+    for format in TextureFormat::ALL {
+        assert_eq!(TextureFormat::from_raw(format as u32), Some(format));
+        assert_eq!(format.to_possible_value().unwrap().get_name(), format.name());
+    }
+    assert_eq!(TextureFormat::from_raw(TextureFormat::ALL.len() as u32), None);
+}
@@ -0,0 +1,82 @@
+// each test binary only uses some of the helpers
+#![allow(dead_code)]
+
+use image::{Rgba, RgbaImage};
+use smb_tex::{Texture, TextureFormat, TextureMeta, TexturePackage};
+
+pub fn meta(id: u32, texture_format: TextureFormat) -> TextureMeta {
+    TextureMeta { id, texture_format, ..Default::default() }
+}
+
+/// A texture without mip levels or raw data
+pub fn texture(id: u32, texture_format: TextureFormat, data: RgbaImage) -> Texture {
+    Texture {
+        meta: meta(id, texture_format),
+        data,
+        mips: Vec::new(),
+        raw: None,
+    }
+}
+
+pub fn single_texture(data: RgbaImage, texture_format: TextureFormat) -> TexturePackage {
+    TexturePackage {
+        textures_ptr: None,
+        textures: vec![texture(0x1234, texture_format, data)],
+    }
+}
+
+/// A package of 4x4 textures with the given ids in that order, each filled with a color of its own
+///
+/// The textures get the mip levels their `mip_count` asks for, so 3 gives them 2x2 and 1x1 ones.
+pub fn package(ids: &[u32], texture_format: TextureFormat, mip_count: u32) -> TexturePackage {
+    let mut builder = TexturePackage::builder();
+    for &id in ids {
+        let meta = TextureMeta { mip_count, ..meta(id, texture_format) };
+        builder = builder.add_texture_with_meta(meta, RgbaImage::from_pixel(4, 4, Rgba([(id * 40) as u8, 0, 0, 0xFF])));
+    }
+    builder.build().unwrap()
+}
+
+/// Builds a package file containing a single texture with the given raw pixel data
+pub fn single_texture_package(width: u32, height: u32, format: TextureFormat, data: &[u8]) -> Vec<u8> {
+    build_single_texture_package(width, height, format, data, u32::to_le_bytes)
+}
+
+/// Same as [`single_texture_package`], but with the headers stored big-endian
+pub fn single_texture_package_be(width: u32, height: u32, format: TextureFormat, data: &[u8]) -> Vec<u8> {
+    build_single_texture_package(width, height, format, data, u32::to_be_bytes)
+}
+
+fn build_single_texture_package(
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data: &[u8],
+    to_bytes: fn(u32) -> [u8; 4],
+) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&to_bytes(1));
+    buf.extend_from_slice(&to_bytes(0x20));
+    buf.resize(0x20, 0);
+    for field in [0x1234, width, height, 0, 0, 0, 0, format as u32, 0x20 + 36] {
+        buf.extend_from_slice(&to_bytes(field));
+    }
+    buf.extend_from_slice(data);
+    buf
+}
+
+/// Creates an empty scratch directory for a test
+pub fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Writes a texture as a `.png` with a `.json` sidecar, as extract does
+pub fn write_texture_files(dir: &std::path::Path, name: &str, meta: &TextureMeta, image: &RgbaImage) {
+    image.save(dir.join(format!("{name}.png"))).unwrap();
+    std::fs::write(dir.join(format!("{name}.json")), serde_json::to_string_pretty(meta).unwrap()).unwrap();
+}
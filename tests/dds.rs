@@ -0,0 +1,31 @@
+mod common;
+
+use binrw::Endian;
+use smb_tex::dds::write_dds;
+use smb_tex::{read_texture_headers, TextureFormat};
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn dds_keeps_packed_pixels_top_down() {
+    // two rows of two R5G6B5 pixels, bottom row first as stored in the package
+    let raw = [1, 0, 2, 0, 3, 0, 4, 0];
+    let package = common::single_texture_package(2, 2, TextureFormat::R5G6B5, &raw);
+    let headers = read_texture_headers(&package).unwrap();
+    let entry = &headers.entries[0];
+
+    let dds = write_dds(&entry.header, entry.raw_data(&package).unwrap(), Endian::Little, false).unwrap();
+
+    assert_eq!(&dds[..4], b"DDS ");
+    assert_eq!(u32_at(&dds, 4), 124);
+    assert_eq!((u32_at(&dds, 12), u32_at(&dds, 16)), (2, 2));
+    // pixel format: 16-bit RGB with 5:6:5 masks and no alpha
+    assert_eq!(u32_at(&dds, 88), 16);
+    assert_eq!(
+        [u32_at(&dds, 92), u32_at(&dds, 96), u32_at(&dds, 100), u32_at(&dds, 104)],
+        [0xF800, 0x07E0, 0x001F, 0]
+    );
+    assert_eq!(&dds[128..], &[3, 0, 4, 0, 1, 0, 2, 0]);
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use binrw::Endian;
+use smb_tex::{
+    decode_texture, encode_texture_with_options, load_texture, read_texture_headers, read_texture_package,
+    read_texture_package_with_endian, ChannelOrder, EncodeOptions, TextureDataArgs, TextureError, TextureFormat,
+};
+
+#[test]
+fn five_bit_channels_are_bit_replicated() {
+    // one row of R5G6B5 pixels, with red and blue running over all 32 five-bit values
+    let data: Vec<u8> = (0..32u16)
+        .flat_map(|v| ((v << 11) | v).to_le_bytes())
+        .collect();
+    let package = common::single_texture_package(32, 1, TextureFormat::R5G6B5, &data);
+    let tp = read_texture_package(&package).unwrap();
+    let image = &tp.textures[0].data;
+
+    for v in 0..32u32 {
+        let expected = ((v << 3) | (v >> 2)) as u8;
+        let pixel = image.get_pixel(v, 0);
+        assert_eq!(pixel[0], expected, "red channel for {v}");
+        assert_eq!(pixel[2], expected, "blue channel for {v}");
+    }
+    assert_eq!(image.get_pixel(31, 0)[0], 0xFF);
+}
+
+#[test]
+fn r5g6b5_pixels_are_opaque() {
+    // every 16-bit value, which includes all zeros and all ones
+    let data: Vec<u8> = (0..=u16::MAX).flat_map(u16::to_le_bytes).collect();
+    for endian in [Endian::Little, Endian::Big] {
+        let package = match endian {
+            Endian::Little => common::single_texture_package(256, 256, TextureFormat::R5G6B5, &data),
+            Endian::Big => common::single_texture_package_be(256, 256, TextureFormat::R5G6B5, &data),
+        };
+        let tp = read_texture_package_with_endian(&package, endian).unwrap();
+        assert!(tp.textures[0].data.pixels().all(|p| p[3] == 0xFF), "{endian:?}");
+    }
+}
+
+#[test]
+fn overflowing_dimensions_are_an_error() {
+    let package = common::single_texture_package(0x10000, 0x10000, TextureFormat::R8G8B8A8, &[]);
+    let err = read_texture_package(&package).unwrap_err();
+    assert!(matches!(err, TextureError::SizeOverflow { width: 0x10000, .. }), "{err:?}");
+}
+
+#[test]
+fn truncated_pixel_data_is_an_error() {
+    let package = common::single_texture_package(2, 2, TextureFormat::R5G6B5, &[0; 5]);
+    let err = read_texture_package(&package).unwrap_err();
+    assert!(matches!(err, TextureError::TruncatedData { expected: 8, actual: 5 }), "{err:?}");
+}
+
+#[test]
+fn data_offsets_outside_the_file_are_reported_with_the_texture() {
+    for offset in [0u32, 0x1000] {
+        let mut package = common::single_texture_package(2, 2, TextureFormat::R5G6B5, &[0; 8]);
+        // the data offset is the last field of the texture header
+        package[0x20 + 32..0x20 + 36].copy_from_slice(&offset.to_le_bytes());
+        let file_size = package.len() as u64;
+
+        let err = read_texture_package(&package).unwrap_err();
+        let expected = matches!(err, TextureError::InvalidDataOffset { id: 0x1234, offset: o, file_size: s } if o == offset && s == file_size);
+        assert!(expected, "{err:?}");
+        let entry = &read_texture_headers(&package).unwrap().entries[0];
+        let err = entry.raw_data(&package).unwrap_err();
+        assert!(matches!(err, TextureError::InvalidDataOffset { id: 0x1234, .. }), "{err:?}");
+        let err = load_texture(&mut std::io::Cursor::new(&package), entry).unwrap_err();
+        assert!(matches!(err, TextureError::InvalidDataOffset { id: 0x1234, .. }), "{err:?}");
+    }
+}
+
+#[test]
+fn unknown_formats_are_reported_with_the_texture() {
+    let mut package = common::single_texture_package(2, 2, TextureFormat::R5G6B5, &[0; 8]);
+    // format field of the only header
+    package[0x3C..0x40].copy_from_slice(&9u32.to_le_bytes());
+    let err = read_texture_package(&package).unwrap_err();
+    assert!(
+        matches!(err, TextureError::UnknownFormat { id: 0x1234, offset: 0x20, format: 9 }),
+        "{err:?}"
+    );
+    let err = read_texture_headers(&package).unwrap_err();
+    assert!(matches!(err, TextureError::UnknownFormat { format: 9, .. }), "{err:?}");
+}
+
+#[test]
+fn big_endian_packages_swap_pixel_bytes() {
+    // pure red followed by pure blue in R5G6B5
+    let data = [0xF8, 0x00, 0x00, 0x1F];
+    let package = common::single_texture_package_be(2, 1, TextureFormat::R5G6B5, &data);
+    let tp = read_texture_package_with_endian(&package, Endian::Big).unwrap();
+    let image = &tp.textures[0].data;
+
+    assert_eq!(tp.textures[0].meta.id, 0x1234);
+    assert_eq!(image.get_pixel(0, 0).0, [0xFF, 0, 0, 0xFF]);
+    assert_eq!(image.get_pixel(1, 0).0, [0, 0, 0xFF, 0xFF]);
+}
+
+#[test]
+fn rgba_rows_are_flipped() {
+    let data: Vec<u8> = (0..2 * 3 * 4).collect();
+    let package = common::single_texture_package(2, 3, TextureFormat::R8G8B8A8, &data);
+    let image = &read_texture_package(&package).unwrap().textures[0].data;
+    // the last stored row is the top one
+    assert_eq!(image.as_raw()[..8], data[16..]);
+    assert_eq!(image.as_raw()[16..], data[..8]);
+}
+
+#[test]
+fn padded_rows_are_skipped() {
+    // 3x2 R5G6B5 rows of 6 bytes, padded to 8
+    let tight: Vec<u8> = (0..12).collect();
+    let mut padded = Vec::new();
+    for row in tight.chunks(6) {
+        padded.extend_from_slice(row);
+        padded.extend_from_slice(&[0xEE, 0xEE]);
+    }
+    let args = |row_stride| TextureDataArgs {
+        width: 3,
+        height: 2,
+        texture_format: TextureFormat::R5G6B5,
+        no_flip: false,
+        channel_order: ChannelOrder::Rgba,
+        row_stride,
+    };
+
+    let expected = decode_texture(&tight, &args(None)).unwrap();
+    assert_eq!(decode_texture(&padded, &args(Some(8))).unwrap(), expected);
+    assert!(matches!(
+        decode_texture(&padded, &args(Some(4))),
+        Err(TextureError::InvalidRowStride { stride: 4, row_size: 6 })
+    ));
+    assert!(matches!(
+        decode_texture(&padded[..14], &args(Some(8))),
+        Err(TextureError::TruncatedData { expected: 16, actual: 14 })
+    ));
+
+    let options = EncodeOptions { row_stride: Some(8), ..EncodeOptions::default() };
+    let encoded = encode_texture_with_options(&expected, TextureFormat::R5G6B5, &options).unwrap();
+    assert_eq!(encoded.len(), 16);
+    assert_eq!(encoded[6..8], [0, 0]);
+    assert_eq!(decode_texture(&encoded, &args(Some(8))).unwrap(), expected);
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use smb_tex::diff::{diff_packages, Difference, Layout};
+use smb_tex::TextureFormat;
+
+#[test]
+fn identical_packages_have_no_differences() {
+    let package = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    assert_eq!(diff_packages(&package, &package).unwrap(), []);
+}
+
+#[test]
+fn changed_pixels_are_counted() {
+    let a = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    let b = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1, 1, 1, 1, 2, 2, 2, 2]);
+    assert_eq!(
+        diff_packages(&a, &b).unwrap(),
+        [Difference::Pixels { id: 0x1234, differing: 1, total: 2 }]
+    );
+}
+
+#[test]
+fn changed_dimensions_are_reported() {
+    let a = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    let b = common::single_texture_package(1, 2, TextureFormat::R8G8B8A8, &[1; 8]);
+    let layout = |width, height| Layout { width, height, texture_format: TextureFormat::R8G8B8A8 };
+    assert_eq!(
+        diff_packages(&a, &b).unwrap(),
+        [Difference::Layout { id: 0x1234, a: layout(2, 1), b: layout(1, 2) }]
+    );
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use image::imageops::FilterType;
+use image::{ImageBuffer, Rgba, RgbaImage};
+use smb_tex::{encode_texture, DirectoryOptions, ManifestEntry, Resize, TextureError, TextureFormat, TextureMeta, TexturePackage};
+
+#[test]
+fn duplicate_ids_are_rejected() {
+    let dir = common::scratch_dir("duplicate_ids_are_rejected");
+    let image = RgbaImage::new(2, 2);
+    common::write_texture_files(&dir, "a", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "b", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "c", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+
+    let err = TexturePackage::from_directory(&dir).unwrap_err().to_string();
+    assert!(err.contains("00000001"), "{err}");
+    assert!(err.contains("a.png") && err.contains("b.png"), "{err}");
+
+    let options = DirectoryOptions {
+        allow_duplicate_ids: true,
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    assert_eq!(tp.textures.len(), 3);
+}
+
+#[test]
+fn recorded_order_takes_precedence_over_ids() {
+    let dir = common::scratch_dir("recorded_order_takes_precedence_over_ids");
+    let image = RgbaImage::new(1, 1);
+    for (name, id, order) in [("a", 1, Some(2)), ("b", 2, None), ("c", 3, Some(0)), ("d", 4, Some(1))] {
+        let meta = TextureMeta {
+            order,
+            ..common::meta(id, TextureFormat::R8G8B8A8)
+        };
+        common::write_texture_files(&dir, name, &meta, &image);
+    }
+
+    let tp = TexturePackage::from_directory(&dir).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [3, 4, 1, 2]);
+}
+
+#[test]
+fn manifest_takes_precedence_over_sidecars() {
+    let dir = common::scratch_dir("manifest_takes_precedence_over_sidecars");
+    let image = RgbaImage::new(1, 1);
+    common::write_texture_files(&dir, "a", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "b", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+
+    let manifest = [ManifestEntry {
+        filename: "a.png".to_string(),
+        meta: common::meta(3, TextureFormat::R5G6B5),
+    }];
+    let options = DirectoryOptions::default();
+    let tp = TexturePackage::from_directory_with_manifest(&dir, &manifest, &options).unwrap();
+    let metas: Vec<_> = tp.textures.iter().map(|t| (t.meta.id, t.meta.texture_format)).collect();
+    assert_eq!(metas, [(2, TextureFormat::R8G8B8A8), (3, TextureFormat::R5G6B5)]);
+
+    let manifest = [ManifestEntry {
+        filename: "missing.png".to_string(),
+        meta: common::meta(4, TextureFormat::R8G8B8A8),
+    }];
+    let err = TexturePackage::from_directory_with_manifest(&dir, &manifest, &options).unwrap_err();
+    assert!(matches!(err, TextureError::MissingManifestFile(_)), "{err}");
+}
+
+#[test]
+fn oversized_textures_are_rejected() {
+    let dir = common::scratch_dir("oversized_textures_are_rejected");
+    common::write_texture_files(&dir, "a", &common::meta(1, TextureFormat::R8G8B8A8), &RgbaImage::new(4, 2));
+    common::write_texture_files(&dir, "b", &common::meta(2, TextureFormat::R8G8B8A8), &RgbaImage::new(2, 9));
+
+    let options = DirectoryOptions {
+        max_dimension: Some(8),
+        ..DirectoryOptions::default()
+    };
+    let err = TexturePackage::from_directory_with_options(&dir, &options).unwrap_err();
+    match err {
+        TextureError::Load { path, source } => {
+            assert!(path.ends_with("b.png"), "{}", path.display());
+            assert!(matches!(*source, TextureError::TextureTooLarge { id: 2, width: 2, height: 9, max: 8 }), "{source}");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    let options = DirectoryOptions {
+        max_dimension: Some(9),
+        ..DirectoryOptions::default()
+    };
+    assert!(TexturePackage::from_directory_with_options(&dir, &options).is_ok());
+}
+
+#[test]
+fn pow2_dimensions_can_be_required() {
+    let dir = common::scratch_dir("pow2_dimensions_can_be_required");
+    common::write_texture_files(&dir, "a", &common::meta(1, TextureFormat::R8G8B8A8), &RgbaImage::new(4, 2));
+    common::write_texture_files(&dir, "b", &common::meta(2, TextureFormat::R8G8B8A8), &RgbaImage::new(4, 3));
+
+    let tp = TexturePackage::from_directory(&dir).unwrap();
+    assert!(tp.textures[0].is_pow2() && !tp.textures[1].is_pow2());
+
+    let options = DirectoryOptions {
+        require_pow2: true,
+        ..DirectoryOptions::default()
+    };
+    let err = TexturePackage::from_directory_with_options(&dir, &options).unwrap_err();
+    match err {
+        TextureError::Load { path, source } => {
+            assert!(path.ends_with("b.png"), "{}", path.display());
+            assert!(matches!(*source, TextureError::NotPowerOfTwo { id: 2, width: 4, height: 3 }), "{source}");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn textures_are_resized_before_the_checks() {
+    let dir = common::scratch_dir("textures_are_resized_before_the_checks");
+    let meta = TextureMeta {
+        mip_count: 2,
+        ..common::meta(1, TextureFormat::R8G8B8A8)
+    };
+    common::write_texture_files(&dir, "a", &meta, &RgbaImage::new(5, 3));
+
+    let options = DirectoryOptions {
+        require_pow2: true,
+        resize: Some(Resize {
+            width: 8,
+            height: 4,
+            filter: FilterType::Nearest,
+        }),
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    assert_eq!(tp.textures[0].data.dimensions(), (8, 4));
+    assert_eq!(tp.textures[0].mips[0].dimensions(), (4, 2));
+}
+
+#[test]
+fn subdirectories_are_loaded_when_recursive() {
+    let dir = common::scratch_dir("subdirectories_are_loaded_when_recursive");
+    let image = RgbaImage::new(1, 1);
+    common::write_texture_files(&dir, "a", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    std::fs::create_dir_all(dir.join("ui/icons")).unwrap();
+    common::write_texture_files(&dir.join("ui"), "b", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir.join("ui/icons"), "c", &common::meta(3, TextureFormat::R8G8B8A8), &image);
+
+    let tp = TexturePackage::from_directory(&dir).unwrap();
+    assert_eq!(tp.textures.len(), 1);
+
+    let options = DirectoryOptions {
+        recursive: true,
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [1, 2, 3]);
+
+    let manifest = [ManifestEntry {
+        filename: "ui/icons/c.png".to_string(),
+        meta: common::meta(4, TextureFormat::R5G6B5),
+    }];
+    let tp = TexturePackage::from_directory_with_manifest(&dir, &manifest, &options).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [1, 2, 4]);
+}
+
+#[test]
+fn directories_are_merged() {
+    let dir = common::scratch_dir("directories_are_merged");
+    let (first, second) = (dir.join("first"), dir.join("second"));
+    std::fs::create_dir_all(&first).unwrap();
+    std::fs::create_dir_all(&second).unwrap();
+    let image = RgbaImage::new(1, 1);
+    common::write_texture_files(&first, "a", &common::meta(3, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&second, "a", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&second, "b", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+
+    let paths = [first.clone(), second.clone()];
+    let tp = TexturePackage::from_directories_with_options(&paths, &DirectoryOptions::default()).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [1, 2, 3]);
+
+    common::write_texture_files(&second, "c", &common::meta(3, TextureFormat::R8G8B8A8), &image);
+    let err = TexturePackage::from_directories_with_options(&paths, &DirectoryOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::DuplicateIds(_)), "{err}");
+}
+
+#[test]
+fn sequential_ids_follow_the_file_names() {
+    let dir = common::scratch_dir("sequential_ids_follow_the_file_names");
+    let image = RgbaImage::new(1, 1);
+    // the same id twice, which the numbering replaces
+    common::write_texture_files(&dir, "b", &common::meta(7, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "a", &common::meta(7, TextureFormat::R5G6B5), &image);
+    common::write_texture_files(&dir, "c", &common::meta(1, TextureFormat::L8), &image);
+
+    let options = DirectoryOptions {
+        sequential_ids: true,
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    let textures: Vec<_> = tp.textures.iter().map(|t| (t.meta.id, t.meta.texture_format)).collect();
+    assert_eq!(textures, [(0, TextureFormat::R5G6B5), (1, TextureFormat::R8G8B8A8), (2, TextureFormat::L8)]);
+}
+
+#[test]
+fn forced_format_reduces_16_bit_images_once() {
+    let dir = common::scratch_dir("forced_format_reduces_16_bit_images_once");
+    // every 16-bit value once, in all channels
+    let image = ImageBuffer::<Rgba<u16>, _>::from_fn(256, 256, |x, y| Rgba([(y * 256 + x) as u16; 4]));
+    image.save(dir.join("a.png")).unwrap();
+    let meta = common::meta(1, TextureFormat::R8G8B8A8);
+    std::fs::write(dir.join("a.json"), serde_json::to_string(&meta).unwrap()).unwrap();
+
+    let options = DirectoryOptions {
+        force_format: Some(TextureFormat::R5G6B5),
+        ..DirectoryOptions::default()
+    };
+    let forced = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    assert_eq!(forced.textures[0].meta.texture_format, TextureFormat::R5G6B5);
+    let direct = smb_tex::texture::from_dynamic_image(image.clone().into(), TextureFormat::R5G6B5);
+    assert_eq!(forced.textures[0].data, direct);
+
+    // changing the format after loading reduces the 8-bit image again, which rounds differently
+    let loaded = TexturePackage::from_directory(&dir).unwrap();
+    let twice = encode_texture(&loaded.textures[0].data, TextureFormat::R5G6B5).unwrap();
+    let once = encode_texture(&direct, TextureFormat::R5G6B5).unwrap();
+    let differing = twice.iter().zip(once.iter()).filter(|(a, b)| a != b).count();
+    assert!(differing > 0);
+}
@@ -0,0 +1,343 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use image::{Rgba, RgbaImage};
+use smb_tex::texture::{
+    data_size, dequantize, encode_png, encode_png_16, from_dynamic_image, premultiply_alpha, quantize, unpremultiply_alpha,
+};
+use smb_tex::{
+    decode_texture, encode_texture_with_options, read_texture_package, write_texture_package,
+    write_texture_package_with_options, ChannelOrder, EncodeOptions, TextureDataArgs, TextureError, TextureFormat,
+    TextureHeader, WriteOptions, PACKAGE_HEADER_SIZE, TEX_HEADER_SIZE,
+};
+
+#[test]
+fn one_bit_alpha_is_thresholded_at_half() {
+    let ramp = RgbaImage::from_fn(256, 1, |x, _| Rgba([0xFF, 0xFF, 0xFF, x as u8]));
+    let data = write_texture_package(&common::single_texture(ramp, TextureFormat::R5G5B5A1)).unwrap();
+    let tp = read_texture_package(&data).unwrap();
+
+    for (x, _, pixel) in tp.textures[0].data.enumerate_pixels() {
+        let expected = if x >= 128 { 0xFF } else { 0 };
+        assert_eq!(pixel[3], expected, "alpha for input {x}");
+    }
+}
+
+#[test]
+fn data_size_rejects_overflowing_dimensions() {
+    assert_eq!(data_size(TextureFormat::R8G8B8A8, 4096, 4096).unwrap(), 64 * 1024 * 1024);
+    assert!(data_size(TextureFormat::R8G8B8A8, 0x8000, 0x8000).is_err());
+    assert!(data_size(TextureFormat::R5G6B5, u32::MAX, 2).is_err());
+    // the size in bytes doesn't even fit into 64 bits
+    assert!(data_size(TextureFormat::R8G8B8A8, 0x8000_0000, 0x8000_0000).is_err());
+}
+
+/// Mean absolute difference between the source ramp and the per-column average of the decoded red channel
+fn ramp_banding(options: &EncodeOptions) -> f64 {
+    let ramp = RgbaImage::from_fn(256, 32, |x, _| Rgba([x as u8, 0, 0, 0xFF]));
+    let data = encode_texture_with_options(&ramp, TextureFormat::R5G6B5, options).unwrap();
+    let args = TextureDataArgs {
+        width: 256,
+        height: 32,
+        texture_format: TextureFormat::R5G6B5,
+        no_flip: false,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    let decoded = decode_texture(&data, &args).unwrap();
+
+    let mut total = 0.0;
+    for x in 0..256 {
+        let average = (0..32).map(|y| decoded.get_pixel(x, y)[0] as f64).sum::<f64>() / 32.0;
+        total += (average - x as f64).abs();
+    }
+    total / 256.0
+}
+
+#[test]
+fn dithering_reduces_banding() {
+    let plain = ramp_banding(&EncodeOptions::default());
+    let dithered = ramp_banding(&EncodeOptions { dither: true, ..EncodeOptions::default() });
+    assert!(dithered < 1.0, "dithered error {dithered}");
+    assert!(dithered * 2.0 < plain, "dithered error {dithered}, plain error {plain}");
+}
+
+#[test]
+fn png_is_tagged_as_srgb() {
+    let image = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 80, y as u8 * 100, 7, 200]));
+    let png = encode_png(&image).unwrap();
+
+    assert!(png.windows(4).any(|w| w == b"sRGB"));
+    let decoded = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap();
+    assert_eq!(decoded.into_rgba8(), image);
+}
+
+#[test]
+fn unpremultiplying_undoes_premultiplying() {
+    let source = RgbaImage::from_fn(256, 1, |x, _| Rgba([200, 100, 7, x as u8]));
+    let mut image = source.clone();
+    premultiply_alpha(&mut image);
+    assert_eq!(image.get_pixel(128, 0).0, [100, 50, 4, 128]);
+    unpremultiply_alpha(&mut image);
+
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    for x in 64..256 {
+        let (expected, actual) = (source.get_pixel(x, 0), image.get_pixel(x, 0));
+        for c in 0..4 {
+            assert!((expected[c] as i32 - actual[c] as i32).abs() <= 2, "{expected:?} -> {actual:?}");
+        }
+    }
+}
+
+#[cfg(feature = "webp")]
+#[test]
+fn webp_is_lossless() {
+    let image = RgbaImage::from_fn(5, 3, |x, y| Rgba([x as u8 * 50, y as u8 * 80, 7, 0xFF]));
+    let webp = smb_tex::texture::encode_webp(&image).unwrap();
+
+    let decoded = image::load_from_memory_with_format(&webp, image::ImageFormat::WebP).unwrap();
+    assert_eq!(decoded.into_rgba8(), image);
+}
+
+#[test]
+fn encoded_size_matches_the_written_package() {
+    let mut tp = common::single_texture(RgbaImage::new(8, 4), TextureFormat::R5G6B5);
+    tp.textures[0].mips = smb_tex::mip::generate_mips(&tp.textures[0].data, 3).unwrap();
+    let data = write_texture_package(&tp).unwrap();
+    assert_eq!(tp.encoded_size().unwrap(), data.len() as u64);
+}
+
+#[test]
+fn empty_textures_are_not_written() {
+    for (width, height) in [(0, 0), (0, 3), (3, 0)] {
+        let tp = common::single_texture(RgbaImage::new(width, height), TextureFormat::R8G8B8A8);
+        let err = write_texture_package(&tp).unwrap_err();
+        assert!(matches!(err, TextureError::EmptyTexture(0x1234)), "{err}");
+    }
+}
+
+#[test]
+fn data_offsets_match_the_written_package() {
+    let mut tp = common::single_texture(RgbaImage::new(4, 4), TextureFormat::R4G4B4A4);
+    tp.textures[0].mips = smb_tex::mip::generate_mips(&tp.textures[0].data, 2).unwrap();
+    tp.textures.push(common::single_texture(RgbaImage::new(2, 2), TextureFormat::R8G8B8A8).textures.remove(0));
+    let data = write_texture_package(&tp).unwrap();
+
+    let written: Vec<_> = smb_tex::read_texture_headers(&data).unwrap().entries.iter()
+        .map(|e| e.data_offset as u64)
+        .collect();
+    assert_eq!(tp.data_offsets().unwrap(), written);
+}
+
+#[test]
+fn aligned_packages_are_padded_and_read_back() {
+    // 2x1 R5G5B5A1 textures have 4 bytes of data, so only the padding keeps them aligned
+    let image = RgbaImage::from_fn(2, 1, |x, _| Rgba([x as u8 * 0xFF, 0, 0, 0xFF]));
+    let mut tp = common::single_texture(image.clone(), TextureFormat::R5G5B5A1);
+    tp.textures.push(common::single_texture(image.clone(), TextureFormat::R5G5B5A1).textures.remove(0));
+    tp.textures.push(common::single_texture(image, TextureFormat::R5G5B5A1).textures.remove(0));
+    let options = WriteOptions {
+        align: 16,
+        ..WriteOptions::default()
+    };
+    let data = write_texture_package_with_options(&tp, &options).unwrap();
+
+    let offsets: Vec<_> = smb_tex::read_texture_headers(&data).unwrap().entries.iter()
+        .map(|e| e.data_offset as u64)
+        .collect();
+    assert!(offsets.iter().all(|offset| offset % 16 == 0), "{offsets:x?}");
+    assert_eq!(tp.data_offsets_with_options(&options).unwrap(), offsets);
+    assert_eq!(tp.encoded_size_with_options(&options).unwrap(), data.len() as u64);
+    assert!(data[offsets[0] as usize + 4..offsets[1] as usize].iter().all(|&b| b == 0));
+
+    let read = read_texture_package(&data).unwrap();
+    for (original, texture) in tp.textures.iter().zip(read.textures.iter()) {
+        assert_eq!(original.data, texture.data);
+    }
+}
+
+#[test]
+fn layout_constants_match_the_serialized_headers() {
+    use binrw::BinWriterExt;
+
+    let header = TextureHeader {
+        id: 1,
+        width: 2,
+        height: 3,
+        unk_c: 0,
+        unk_10: 0,
+        unk_14: 0,
+        unk_18: 0,
+        texture_format: TextureFormat::R8G8B8A8,
+    };
+    let mut cur = std::io::Cursor::new(Vec::new());
+    cur.write_le(&header).unwrap();
+    cur.write_le(&0u32).unwrap();
+    assert_eq!(cur.into_inner().len() as u32, TEX_HEADER_SIZE);
+
+    let data = write_texture_package(&common::single_texture(RgbaImage::new(2, 2), TextureFormat::R8G8B8A8)).unwrap();
+    let headers = smb_tex::read_texture_headers(&data).unwrap();
+    assert_eq!(headers.entries[0].data_offset, PACKAGE_HEADER_SIZE + TEX_HEADER_SIZE);
+    assert_eq!(data.len() as u32, PACKAGE_HEADER_SIZE + TEX_HEADER_SIZE + 16);
+}
+
+#[test]
+fn grayscale_formats_keep_a_single_channel() {
+    let image = RgbaImage::from_fn(16, 4, |x, y| Rgba([x as u8 * 16, x as u8 * 16, x as u8 * 16, y as u8 * 64]));
+    let args = |texture_format| TextureDataArgs {
+        width: 16,
+        height: 4,
+        texture_format,
+        no_flip: true,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    let options = EncodeOptions { no_flip: true, ..Default::default() };
+
+    let data = encode_texture_with_options(&image, TextureFormat::L8, &options).unwrap();
+    assert_eq!(data.len(), 16 * 4);
+    let decoded = decode_texture(&data, &args(TextureFormat::L8)).unwrap();
+    for (original, pixel) in image.pixels().zip(decoded.pixels()) {
+        assert_eq!(pixel.0, [original[0], original[0], original[0], 0xFF]);
+    }
+
+    let data = encode_texture_with_options(&image, TextureFormat::A8, &options).unwrap();
+    assert_eq!(data.len(), 16 * 4);
+    let decoded = decode_texture(&data, &args(TextureFormat::A8)).unwrap();
+    for (original, pixel) in image.pixels().zip(decoded.pixels()) {
+        assert_eq!(pixel.0, [0xFF, 0xFF, 0xFF, original[3]]);
+    }
+}
+
+#[test]
+fn quantization_is_stable() {
+    for bits in 1..=8 {
+        for level in 0..1u16 << bits {
+            assert_eq!(quantize(dequantize(level, bits), bits), level, "level {level} with {bits} bits");
+        }
+        for value in 0..=0xFF {
+            let once = dequantize(quantize(value, bits), bits);
+            let twice = dequantize(quantize(once, bits), bits);
+            assert_eq!(once, twice, "value {value} with {bits} bits");
+            // rounding keeps every value within half a level (and the error of bit replication) of its quantized one
+            assert!((once as f32 - value as f32).abs() <= 255.0 / ((1 << bits) - 1) as f32 / 2.0 + 1.0);
+        }
+    }
+}
+
+#[test]
+fn packages_do_not_drift_when_rewritten() {
+    let image = RgbaImage::from_fn(64, 4, |x, y| Rgba([x as u8 * 4 + 1, 255 - x as u8 * 4, y as u8 * 60 + 7, x as u8 * 3]));
+    for format in [TextureFormat::R5G5B5A1, TextureFormat::R4G4B4A4, TextureFormat::R5G6B5] {
+        let once = read_texture_package(&write_texture_package(&common::single_texture(image.clone(), format)).unwrap()).unwrap();
+        let twice = read_texture_package(&write_texture_package(&once).unwrap()).unwrap();
+        assert_eq!(once.textures[0].data, twice.textures[0].data, "{format:?}");
+    }
+}
+
+#[test]
+fn writing_is_deterministic() {
+    let gradient = RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8 * 4, y as u8 * 4, (x * y) as u8, (x + y) as u8 * 2]));
+    let mut tp = common::single_texture(gradient.clone(), TextureFormat::R5G6B5);
+    tp.textures[0].mips = smb_tex::mip::generate_mips(&gradient, 3).unwrap();
+    for format in [TextureFormat::R4G4B4A4, TextureFormat::P8, TextureFormat::R8G8B8A8] {
+        tp.textures.push(common::single_texture(gradient.clone(), format).textures.remove(0));
+    }
+    let mut options = WriteOptions::default();
+    options.encode.dither = true;
+    options.align = 64;
+
+    let first = write_texture_package_with_options(&tp, &options).unwrap();
+    let second = write_texture_package_with_options(&tp, &options).unwrap();
+    // not assert_eq, to not print the whole package
+    assert!(first == second);
+}
+
+#[test]
+fn reserved_header_bytes_move_the_texture_headers() {
+    let tp = common::single_texture(RgbaImage::new(2, 2), TextureFormat::R8G8B8A8);
+    let options = WriteOptions {
+        header_reserve: 0x10,
+        ..WriteOptions::default()
+    };
+    let data = write_texture_package_with_options(&tp, &options).unwrap();
+
+    assert_eq!(data[4..8], 0x30u32.to_le_bytes());
+    assert!(data[8..0x30].iter().all(|&b| b == 0));
+    assert_eq!(tp.encoded_size_with_options(&options).unwrap(), data.len() as u64);
+    let read = read_texture_package(&data).unwrap();
+    assert_eq!(read.textures[0].meta.id, 0x1234);
+    assert_eq!(read.textures[0].data, tp.textures[0].data);
+}
+
+#[test]
+fn progress_is_reported_for_every_texture() {
+    let mut tp = common::single_texture(RgbaImage::new(2, 2), TextureFormat::R8G8B8A8);
+    tp.textures.push(common::texture(0x5678, TextureFormat::L8, RgbaImage::new(2, 2)));
+    let count = Arc::new(AtomicUsize::new(0));
+    let counted = count.clone();
+    let options = WriteOptions {
+        progress: Some(Arc::new(move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        })),
+        ..WriteOptions::default()
+    };
+    write_texture_package_with_options(&tp, &options).unwrap();
+    assert_eq!(count.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn textures_ptr_is_kept_when_rewriting() {
+    let tp = common::single_texture(RgbaImage::new(2, 2), TextureFormat::R8G8B8A8);
+    let options = WriteOptions {
+        header_reserve: 0x10,
+        ..WriteOptions::default()
+    };
+    let data = write_texture_package_with_options(&tp, &options).unwrap();
+
+    let mut read = read_texture_package(&data).unwrap();
+    assert_eq!(read.textures_ptr, Some(0x30));
+    assert_eq!(write_texture_package(&read).unwrap(), data);
+
+    read.textures_ptr = Some(4);
+    assert!(matches!(write_texture_package(&read), Err(TextureError::InvalidTexturesPtr(4))));
+}
+
+#[test]
+fn sixteen_bit_pngs_keep_the_packed_values() {
+    let noise = RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 17) as u8, (y * 13) as u8, (x * y) as u8, (x + y * 16) as u8]));
+    for format in [TextureFormat::R5G5B5A1, TextureFormat::R4G4B4A4, TextureFormat::R5G6B5] {
+        let data = write_texture_package(&common::single_texture(noise.clone(), format)).unwrap();
+        let decoded = read_texture_package(&data).unwrap().textures.remove(0).data;
+
+        let png = encode_png_16(&decoded, format).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert!(matches!(image, image::DynamicImage::ImageRgba16(_)));
+        assert_eq!(from_dynamic_image(image, format), decoded, "{:?}", format);
+    }
+}
+
+#[test]
+fn bgra_textures_have_red_and_blue_swapped() {
+    let image = RgbaImage::from_pixel(1, 1, Rgba([0xFF, 0x80, 0x00, 0xFF]));
+    let options = EncodeOptions { channel_order: ChannelOrder::Bgra, ..EncodeOptions::default() };
+    let args = |texture_format| TextureDataArgs {
+        width: 1,
+        height: 1,
+        texture_format,
+        no_flip: false,
+        channel_order: ChannelOrder::Bgra,
+        row_stride: None,
+    };
+
+    let data = encode_texture_with_options(&image, TextureFormat::R8G8B8A8, &options).unwrap();
+    assert_eq!(data, [0x00, 0x80, 0xFF, 0xFF]);
+    assert_eq!(decode_texture(&data, &args(TextureFormat::R8G8B8A8)).unwrap(), image);
+
+    // blue in the top 5 bits
+    let data = encode_texture_with_options(&image, TextureFormat::R5G6B5, &options).unwrap();
+    assert_eq!(u16::from_le_bytes([data[0], data[1]]), 0x20 << 5 | 0x1F);
+    let decoded = decode_texture(&data, &args(TextureFormat::R5G6B5)).unwrap();
+    assert_eq!(decoded.get_pixel(0, 0).0, [0xFF, 0x82, 0x00, 0xFF]);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use binrw::Endian;
+use smb_tex::ktx2::{read_ktx2, write_ktx2};
+use smb_tex::{read_texture_headers, TextureError, TextureFormat};
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn ktx2_keeps_packed_pixels_top_down() {
+    // two rows of two R5G6B5 pixels, bottom row first as stored in the package
+    let raw = [1, 0, 2, 0, 3, 0, 4, 0];
+    let package = common::single_texture_package(2, 2, TextureFormat::R5G6B5, &raw);
+    let headers = read_texture_headers(&package).unwrap();
+    let entry = &headers.entries[0];
+
+    let ktx2 = write_ktx2(&entry.header, entry.raw_data(&package).unwrap(), Endian::Little, false).unwrap();
+
+    assert_eq!(&ktx2[..12], b"\xABKTX 20\xBB\r\n\x1A\n");
+    // VK_FORMAT_R5G6B5_UNORM_PACK16
+    assert_eq!(u32_at(&ktx2, 12), 4);
+    assert_eq!((u32_at(&ktx2, 20), u32_at(&ktx2, 24)), (2, 2));
+    let data_offset = u32_at(&ktx2, 80) as usize;
+    assert_eq!(&ktx2[data_offset..], &[3, 0, 4, 0, 1, 0, 2, 0]);
+
+    let (args, data) = read_ktx2(&ktx2, false).unwrap();
+    assert_eq!((args.width, args.height, args.texture_format), (2, 2, TextureFormat::R5G6B5));
+    assert_eq!(data, raw);
+}
+
+#[test]
+fn level_offsets_past_the_end_are_an_error() {
+    let package = common::single_texture_package(2, 2, TextureFormat::R5G6B5, &[0; 8]);
+    let headers = read_texture_headers(&package).unwrap();
+    let entry = &headers.entries[0];
+    let mut ktx2 = write_ktx2(&entry.header, entry.raw_data(&package).unwrap(), Endian::Little, false).unwrap();
+    ktx2[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let err = read_ktx2(&ktx2, false).unwrap_err();
+    assert!(matches!(err, TextureError::TruncatedData { expected: 8, actual: 0 }), "{err}");
+    // an empty level doesn't need any data, but its offset still has to be inside the file
+    ktx2[20..28].fill(0);
+    let err = read_ktx2(&ktx2, false).unwrap_err();
+    assert!(matches!(err, TextureError::TruncatedData { expected: 0, actual: 0 }), "{err}");
+}
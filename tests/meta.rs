@@ -0,0 +1,131 @@
+mod common;
+
+use smb_tex::meta::{find_meta, read_meta, set_meta_id, write_meta, MetaFormat, PrettyMeta};
+use smb_tex::texture::ManifestEntry;
+use smb_tex::{MetaError, Texture, TextureError, TextureFlags, TextureFormat, WrapMode};
+
+#[test]
+fn wrap_modes_keep_unknown_values() {
+    let mut meta = common::meta(1, TextureFormat::R8G8B8A8);
+    meta.unk_c = 1;
+    meta.unk_10 = 0x2901;
+    assert_eq!(meta.wrap_u(), WrapMode::Clamp);
+    assert_eq!(meta.wrap_v(), WrapMode::Unknown(0x2901));
+
+    meta.set_wrap_u(WrapMode::Mirror);
+    meta.set_wrap_v(meta.wrap_v());
+    assert_eq!((meta.unk_c, meta.unk_10), (2, 0x2901));
+}
+
+#[test]
+fn flags_cover_all_bits() {
+    let mut meta = common::meta(1, TextureFormat::R8G8B8A8);
+    meta.set_flags(TextureFlags(0x8000_0001));
+    assert_eq!(meta.unk_18, i32::MIN + 1);
+    assert!(meta.flags().is_set(0) && meta.flags().is_set(31));
+    assert!(!meta.flags().is_set(1) && !meta.flags().is_set(32));
+}
+
+#[test]
+fn pretty_meta_round_trips() {
+    let mut meta = common::meta(0x0a, TextureFormat::R5G6B5);
+    meta.unk_c = 1;
+    meta.unk_10 = 0x2901;
+    meta.unk_14 = -3;
+    meta.unk_18 = 5;
+
+    let json = serde_json::to_value(PrettyMeta::from(meta)).unwrap();
+    assert_eq!(json["id"], "0000000a");
+    assert_eq!(json["format"], "R5G6B5");
+    assert_eq!(json["wrap_u"], "clamp");
+    assert_eq!(json["wrap_v"], 0x2901);
+
+    let parsed = read_meta(json.to_string().as_bytes(), MetaFormat::Json).unwrap();
+    assert_eq!(
+        (parsed.id, parsed.unk_c, parsed.unk_10, parsed.unk_14, parsed.unk_18),
+        (0x0a, 1, 0x2901, -3, 5)
+    );
+    assert_eq!(parsed.texture_format, TextureFormat::R5G6B5);
+}
+
+#[test]
+fn both_meta_forms_are_accepted() {
+    let raw = serde_json::to_value(common::meta(0x1f, TextureFormat::R4G4B4A4)).unwrap();
+    assert_eq!(read_meta(raw.to_string().as_bytes(), MetaFormat::Json).unwrap().id, 0x1f);
+
+    let manifest = r#"[
+        {"filename": "a.png", "id": 1, "unk_c": 0, "unk_10": 0, "unk_14": 0, "unk_18": 0, "texture_format": "R8G8B8A8"},
+        {"filename": "b.png", "schema_version": 1, "id": "2", "format": "L8", "wrap_u": "mirror", "wrap_v": "repeat", "flags": 0, "unk_14": 0}
+    ]"#;
+    let entries: Vec<ManifestEntry> = serde_json::from_str(manifest).unwrap();
+    assert_eq!((entries[0].meta.id, entries[0].meta.texture_format), (1, TextureFormat::R8G8B8A8));
+    assert_eq!((entries[1].meta.id, entries[1].meta.unk_c), (2, 2));
+    assert_eq!(entries[1].filename, "b.png");
+}
+
+#[test]
+fn unknown_schema_versions_are_rejected() {
+    let mut json = serde_json::to_value(PrettyMeta::from(common::meta(1, TextureFormat::R8G8B8A8))).unwrap();
+    json["schema_version"] = 2.into();
+    assert!(read_meta(json.to_string().as_bytes(), MetaFormat::Json).is_err());
+    let text = write_meta(&common::meta(1, TextureFormat::R8G8B8A8), true, MetaFormat::Toml).replace("schema_version = 1", "schema_version = 2");
+    assert!(read_meta(text.as_bytes(), MetaFormat::Toml).is_err());
+}
+
+#[test]
+fn toml_meta_round_trips() {
+    let mut meta = common::meta(0x0a, TextureFormat::R4G4B4A4);
+    meta.unk_14 = -3;
+    meta.order = Some(2);
+    // doesn't fit into the signed integers of TOML
+    meta.image_hash = Some(u64::MAX - 1);
+
+    for pretty in [false, true] {
+        let text = write_meta(&meta, pretty, MetaFormat::Toml);
+        assert!(text.contains("image_hash = \"fffffffffffffffe\""), "{text}");
+        let read = read_meta(text.as_bytes(), MetaFormat::Toml).unwrap();
+        assert_eq!(serde_json::to_value(read).unwrap(), serde_json::to_value(meta).unwrap());
+    }
+    assert!(read_meta(b"id = ", MetaFormat::Toml).is_err());
+}
+
+#[test]
+fn both_sidecars_are_an_error() {
+    let dir = common::scratch_dir("both_sidecars");
+    let meta = common::meta(0x0a, TextureFormat::R8G8B8A8);
+    common::write_texture_files(&dir, "0000000a", &meta, &image::RgbaImage::new(2, 2));
+    let image = dir.join("0000000a.png");
+    assert_eq!(find_meta(&image).unwrap(), (dir.join("0000000a.json"), MetaFormat::Json));
+
+    std::fs::write(dir.join("0000000a.toml"), write_meta(&meta, false, MetaFormat::Toml)).unwrap();
+    assert!(matches!(find_meta(&image), Err(TextureError::AmbiguousMeta(_))));
+    assert!(matches!(Texture::from_files(&image), Err(TextureError::AmbiguousMeta(_))));
+}
+
+#[test]
+fn meta_errors_point_at_the_problem() {
+    let err = read_meta(b"{\n  \"id\": 1,\n  \"texture_format\": \"RGB\"\n}", MetaFormat::Json).unwrap_err();
+    assert!(matches!(&err, MetaError::Json(err) if err.line() == 3), "{err}");
+    let text = "id = 1\ntexture_format = \"RGB\"\n";
+    let err = read_meta(text.as_bytes(), MetaFormat::Toml).unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{err}");
+    let MetaError::Toml(err) = err else { panic!("expected a TOML error, got {err}") };
+    assert_eq!(&text[err.span().unwrap()], "\"RGB\"");
+
+    for (text, format) in [(&b"{\"id\": 1}"[..], MetaFormat::Json), (b"schema_version = 1\nid = \"01\"", MetaFormat::Toml)] {
+        let err = read_meta(text, format).unwrap_err();
+        assert!(err.to_string().contains("`R5G6B5`"), "{err}");
+    }
+}
+
+#[test]
+fn setting_the_id_keeps_the_meta_form() {
+    let meta = common::meta(0x30, TextureFormat::R5G6B5);
+    for format in [MetaFormat::Json, MetaFormat::Toml] {
+        for pretty in [false, true] {
+            let text = set_meta_id(write_meta(&meta, pretty, format).as_bytes(), format, 2).unwrap();
+            let expected = smb_tex::TextureMeta { id: 2, ..meta };
+            assert_eq!(text, write_meta(&expected, pretty, format));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::mip::{downsample_with_gamma, generate_mips, generate_mips_with_gamma, max_mip_count, MipGamma};
+use smb_tex::{read_texture_headers, write_texture_package, TextureFormat};
+
+#[test]
+fn mip_chain_halves_down_to_one_pixel() {
+    let base = RgbaImage::from_fn(4, 2, |x, _| Rgba([x as u8 * 40, 0, 0, 0xFF]));
+    assert_eq!(max_mip_count(4, 2), 3);
+
+    let mips = generate_mips(&base, 3).unwrap();
+    let dimensions: Vec<_> = mips.iter().map(|m| m.dimensions()).collect();
+    assert_eq!(dimensions, [(2, 1), (1, 1)]);
+    // box filter: (0 + 40) / 2 and (80 + 120) / 2, then the average of those
+    assert_eq!(mips[0].get_pixel(0, 0)[0], 20);
+    assert_eq!(mips[0].get_pixel(1, 0)[0], 100);
+    assert_eq!(mips[1].get_pixel(0, 0)[0], 60);
+
+    assert!(generate_mips(&base, 4).is_err());
+    assert!(generate_mips(&base, 0).is_err());
+}
+
+#[test]
+fn mip_levels_are_written_after_the_base_level() {
+    let base = RgbaImage::new(4, 4);
+    let mut tp = common::single_texture(base.clone(), TextureFormat::R8G8B8A8);
+    tp.textures[0].mips = generate_mips(&base, 3).unwrap();
+    tp.textures.push(common::single_texture(base, TextureFormat::R8G8B8A8).textures.remove(0));
+
+    let data = write_texture_package(&tp).unwrap();
+    let headers = read_texture_headers(&data).unwrap();
+    let offsets: Vec<_> = headers.entries.iter().map(|e| e.data_offset).collect();
+    assert_eq!(offsets[1] - offsets[0], (16 + 4 + 1) * 4);
+    assert_eq!(data.len() as u32, offsets[1] + 16 * 4);
+}
+
+#[test]
+fn linear_mips_keep_the_brightness() {
+    // black and white stripes average to a darker gray in sRGB than in linear light
+    let stripes = RgbaImage::from_fn(2, 2, |x, _| if x == 0 { Rgba([0, 0, 0, 0]) } else { Rgba([0xFF; 4]) });
+    assert_eq!(downsample_with_gamma(&stripes, MipGamma::Srgb).get_pixel(0, 0).0, [128; 4]);
+    // alpha is averaged as is
+    assert_eq!(downsample_with_gamma(&stripes, MipGamma::Linear).get_pixel(0, 0).0, [188, 188, 188, 128]);
+
+    // flat colors come back exactly from linear light
+    for value in 0..=255 {
+        let flat = RgbaImage::from_pixel(2, 2, Rgba([value, value, value, 0xFF]));
+        let mips = generate_mips_with_gamma(&flat, 2, MipGamma::Linear).unwrap();
+        assert_eq!(mips[0].get_pixel(0, 0), flat.get_pixel(0, 0));
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::montage::montage;
+use smb_tex::{Texture, TextureFormat};
+
+fn texture(id: u32, width: u32, height: u32, color: [u8; 3]) -> Texture {
+    let image = RgbaImage::from_pixel(width, height, Rgba([color[0], color[1], color[2], 0xFF]));
+    common::texture(id, TextureFormat::R8G8B8A8, image)
+}
+
+/// Bounding box (min x, min y, max x, max y) of the pixels with the given color
+fn find(image: &RgbaImage, color: [u8; 3]) -> Option<(u32, u32, u32, u32)> {
+    image.enumerate_pixels()
+        .filter(|(_, _, p)| p.0 == [color[0], color[1], color[2], 0xFF])
+        .fold(None, |bounds, (x, y, _)| Some(match bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        }))
+}
+
+#[test]
+fn textures_are_scaled_into_a_grid_in_order() {
+    let colors = [[0xFF, 0, 0], [0, 0xFF, 0], [0, 0, 0xFF], [0xFF, 0xFF, 0], [0, 0xFF, 0xFF]];
+    let sizes = [(4, 4), (64, 16), (1, 1), (8, 2), (100, 100)];
+    let textures: Vec<_> = colors.iter().zip(sizes).enumerate()
+        .map(|(i, (&color, (w, h)))| texture(i as u32, w, h, color))
+        .collect();
+    let image = montage(&textures, 32, 0);
+
+    let bounds: Vec<_> = colors.iter().map(|&c| find(&image, c).unwrap()).collect();
+    for &(x0, y0, x1, y1) in bounds.iter() {
+        assert_eq!(x1 - x0 + 1, 32, "every texture is scaled to the thumbnail width");
+        assert!(y1 - y0 < 32);
+    }
+    // 3 textures per row, in package order
+    assert!(bounds[0].0 < bounds[1].0 && bounds[1].0 < bounds[2].0);
+    assert_eq!(bounds[3].0, bounds[0].0);
+    assert!(bounds[3].1 > bounds[0].3);
+    // the aspect ratio is kept
+    assert_eq!(bounds[1].3 - bounds[1].1 + 1, 8);
+}
+
+#[test]
+fn single_column_montages_are_stacked() {
+    let textures = [texture(1, 2, 2, [0xFF, 0, 0]), texture(2, 2, 2, [0, 0xFF, 0])];
+    let image = montage(&textures, 16, 1);
+
+    assert!(image.height() > image.width());
+    let (first, second) = (find(&image, [0xFF, 0, 0]).unwrap(), find(&image, [0, 0xFF, 0]).unwrap());
+    assert_eq!(first.0, second.0);
+    assert!(second.1 > first.3);
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::collections::BTreeMap;
+use image::RgbaImage;
+use smb_tex::names::{id_for_name, read_name_map, NameHash, NameMap, NameTemplate};
+use smb_tex::{DirectoryOptions, TextureError, TextureFormat, TexturePackage};
+
+#[test]
+fn unmapped_ids_fall_back_to_hex() {
+    let names = NameMap::new(BTreeMap::from([(0xa, "menu_background".to_string())])).unwrap();
+    assert_eq!(names.name(0xa), "menu_background");
+    assert_eq!(names.name(0xb), "0000000b");
+    assert_eq!(names.id("menu_background"), Some(0xa));
+    assert_eq!(names.id("0000000b"), None);
+}
+
+#[test]
+fn invalid_names_are_rejected() {
+    for names in [
+        BTreeMap::from([(1, "a/b".to_string())]),
+        BTreeMap::from([(1, "..".to_string())]),
+        BTreeMap::from([(1, "same".to_string()), (2, "same".to_string())]),
+    ] {
+        let err = NameMap::new(names).unwrap_err();
+        assert!(matches!(err, TextureError::InvalidNameMap(_)), "{err}");
+    }
+}
+
+#[test]
+fn malformed_name_map_files_are_reported_as_such() {
+    let dir = common::scratch_dir("malformed_name_map_files_are_reported_as_such");
+    let path = dir.join("names.json");
+    std::fs::write(&path, "{\"0000000a\": 1}").unwrap();
+
+    let err = read_name_map(&path).unwrap_err();
+    assert!(matches!(&err, TextureError::InvalidNameMapFile { path: p, .. } if *p == path), "{err}");
+    assert!(err.to_string().starts_with("Invalid name map file"), "{err}");
+}
+
+#[test]
+fn names_give_ids_when_creating() {
+    let dir = common::scratch_dir("names_give_ids_when_creating");
+    let image = RgbaImage::new(1, 1);
+    common::write_texture_files(&dir, "logo", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "00000002", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+    let map_path = dir.join("names.json");
+    std::fs::write(&map_path, r#"{"0x30": "logo", "00000040": "unused"}"#).unwrap();
+
+    let options = DirectoryOptions {
+        names: read_name_map(&map_path).unwrap(),
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [2, 0x30]);
+}
+
+#[test]
+fn templates_name_textures_and_parse_ids_back() {
+    let template = NameTemplate::new("tex{id}_{format}").unwrap();
+    assert_eq!(template.name(48, 0, TextureFormat::R5G6B5), "tex48_r5g6b5");
+    assert_eq!(template.id("tex48_r5g6b5"), Some(48));
+    assert_eq!(template.id("tex48_rgb"), None);
+    assert_eq!(template.id("48_r5g6b5"), None);
+
+    // the digits of the id can run into the text after it
+    let template = NameTemplate::new("{id:x}0").unwrap();
+    assert_eq!(template.name(0xab, 0, TextureFormat::L8), "ab0");
+    assert_eq!(template.id("ab0"), Some(0xab));
+    assert_eq!(NameTemplate::default().name(0xab, 3, TextureFormat::L8), "000000ab");
+
+    // the index is written but ignored when parsing, however many digits it has
+    let template = NameTemplate::new("{index:03}_{id:08x}").unwrap();
+    assert_eq!(template.name(0x30, 7, TextureFormat::L8), "007_00000030");
+    assert_eq!(template.name(0x30, 1234, TextureFormat::L8), "1234_00000030");
+    assert_eq!(template.id("1234_00000030"), Some(0x30));
+    assert_eq!(template.id("07_00000030"), None);
+    let template = NameTemplate::new("{index}{id}").unwrap();
+    assert_eq!(template.name(48, 12, TextureFormat::L8), "1248");
+
+    for template in ["tex", "{index}", "{id}{id:x}", "{name}", "{index:3}", "{id", "{id}}", "ui/{id}"] {
+        let err = NameTemplate::new(template).unwrap_err();
+        assert!(matches!(err, TextureError::InvalidNameTemplate(_)), "{err}");
+    }
+}
+
+#[test]
+fn templates_give_ids_when_creating() {
+    let dir = common::scratch_dir("templates_give_ids_when_creating");
+    let image = RgbaImage::new(1, 1);
+    common::write_texture_files(&dir, "48_r8g8b8a8", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "logo", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+
+    let options = DirectoryOptions {
+        name_template: Some(NameTemplate::new("{id}_{format}").unwrap()),
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [2, 48]);
+}
+
+#[test]
+fn hashed_names_give_ids_when_creating() {
+    assert_eq!(id_for_name("menu_background"), 0x9dc50082);
+    assert_eq!(NameHash::Crc32.id("menu_background"), 0x9dc50082);
+    assert_eq!(NameHash::Fnv1a.id("a"), 0xe40c292c);
+
+    let dir = common::scratch_dir("hashed_names_give_ids_when_creating");
+    let image = RgbaImage::new(1, 1);
+    common::write_texture_files(&dir, "menu_background", &common::meta(1, TextureFormat::R8G8B8A8), &image);
+    common::write_texture_files(&dir, "logo", &common::meta(2, TextureFormat::R8G8B8A8), &image);
+
+    let options = DirectoryOptions {
+        names: NameMap::new(BTreeMap::from([(0x30, "logo".to_string())])).unwrap(),
+        id_from_name: Some(id_for_name),
+        ..DirectoryOptions::default()
+    };
+    let tp = TexturePackage::from_directory_with_options(&dir, &options).unwrap();
+    let ids: Vec<_> = tp.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [0x30, 0x9dc50082]);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::palette::{build_palette, PALETTE_SIZE};
+use smb_tex::texture::data_size;
+use smb_tex::{read_texture_package, write_texture_package, TextureFormat};
+
+#[test]
+fn few_colors_round_trip_exactly() {
+    let image = RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 16) as u8, (y * 16) as u8, 0x80, (x ^ y) as u8 * 16]));
+    let data = write_texture_package(&common::single_texture(image.clone(), TextureFormat::P8)).unwrap();
+    let tp = read_texture_package(&data).unwrap();
+
+    assert_eq!(tp.textures[0].meta.texture_format, TextureFormat::P8);
+    assert_eq!(tp.textures[0].data, image);
+}
+
+#[test]
+fn many_colors_are_reduced_to_the_palette() {
+    let image = RgbaImage::from_fn(64, 64, |x, y| Rgba([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8, 0xFF]));
+    assert!(build_palette(&image, 256).len() <= 256);
+
+    let data = write_texture_package(&common::single_texture(image.clone(), TextureFormat::P8)).unwrap();
+    let tp = read_texture_package(&data).unwrap();
+    let decoded = &tp.textures[0].data;
+
+    let error: u64 = image.pixels().zip(decoded.pixels())
+        .flat_map(|(a, b)| (0..4).map(move |c| (a[c] as i64 - b[c] as i64).unsigned_abs()))
+        .sum();
+    let mean = error as f64 / (64.0 * 64.0 * 4.0);
+    assert!(mean < 4.0, "mean channel error {mean}");
+}
+
+#[test]
+fn data_size_includes_the_palette() {
+    assert_eq!(data_size(TextureFormat::P8, 64, 32).unwrap() as usize, PALETTE_SIZE + 64 * 32);
+    assert_eq!(data_size(TextureFormat::P8, 0, 0).unwrap() as usize, PALETTE_SIZE);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::raw::{read_raw_dump, write_raw_dump, DATA_FILE_NAME, TOC_FILE_NAME};
+use smb_tex::{DirectoryOptions, TextureError, TextureFormat, TextureMeta};
+
+#[test]
+fn raw_dump_round_trip() {
+    let dir = common::scratch_dir("raw_dump_round_trip");
+    let a = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8, y as u8, 7, 255]));
+    let b = RgbaImage::from_pixel(1, 4, Rgba([1, 2, 3, 4]));
+    let textures = [
+        (TextureMeta { order: Some(0), ..common::meta(2, TextureFormat::R5G6B5) }, a.clone()),
+        (TextureMeta { order: Some(1), ..common::meta(1, TextureFormat::R8G8B8A8) }, b.clone()),
+    ];
+    write_raw_dump(&dir, &textures).unwrap();
+    assert_eq!(std::fs::metadata(dir.join(DATA_FILE_NAME)).unwrap().len(), (6 + 4) * 4);
+
+    let tp = read_raw_dump(&dir, &DirectoryOptions::default()).unwrap();
+    let loaded: Vec<_> = tp.textures.iter().map(|t| (t.meta.id, t.meta.texture_format, t.data.clone())).collect();
+    assert_eq!(loaded, [(2, TextureFormat::R5G6B5, a), (1, TextureFormat::R8G8B8A8, b)]);
+}
+
+#[test]
+fn truncated_raw_dump_is_rejected() {
+    let dir = common::scratch_dir("truncated_raw_dump_is_rejected");
+    let textures = [(common::meta(1, TextureFormat::R8G8B8A8), RgbaImage::new(2, 2))];
+    write_raw_dump(&dir, &textures).unwrap();
+    std::fs::write(dir.join(DATA_FILE_NAME), [0; 10]).unwrap();
+
+    let err = read_raw_dump(&dir, &DirectoryOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::TruncatedData { expected: 16, actual: 10 }), "{err}");
+}
+
+#[test]
+fn malformed_raw_toc_is_reported_as_such() {
+    let dir = common::scratch_dir("malformed_raw_toc_is_reported_as_such");
+    write_raw_dump(&dir, &[(common::meta(1, TextureFormat::R8G8B8A8), RgbaImage::new(2, 2))]).unwrap();
+    std::fs::write(dir.join(TOC_FILE_NAME), "[{}]").unwrap();
+
+    let err = read_raw_dump(&dir, &DirectoryOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::InvalidRawToc { .. }), "{err}");
+}
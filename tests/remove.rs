@@ -0,0 +1,38 @@
+mod common;
+
+use smb_tex::verify::verify_package;
+use smb_tex::{read_texture_package, remove_textures, write_texture_package, TextureError, TextureFormat, TexturePackage};
+
+fn package(ids: &[u32]) -> TexturePackage {
+    common::package(ids, TextureFormat::R8G8B8A8, 1)
+}
+
+#[test]
+fn removed_textures_and_their_data_are_dropped() {
+    let data = write_texture_package(&package(&[1, 2, 3, 4])).unwrap();
+
+    let result = remove_textures(&data, &[1, 3]).unwrap();
+    assert_eq!(verify_package(&result).unwrap(), []);
+    assert_eq!(result, write_texture_package(&package(&[2, 4])).unwrap());
+    let tp = read_texture_package(&result).unwrap();
+    assert_eq!(tp.textures[1].data, package(&[4]).textures[0].data);
+}
+
+#[test]
+fn removing_a_missing_id_fails() {
+    let data = write_texture_package(&package(&[1, 2])).unwrap();
+
+    let err = remove_textures(&data, &[2, 5]).unwrap_err();
+    assert!(matches!(err, TextureError::TextureNotFound(5)), "{err}");
+}
+
+#[test]
+fn data_offsets_past_the_end_are_an_error() {
+    let mut data = write_texture_package(&package(&[1, 2])).unwrap();
+    // the data offset of the second texture
+    let field = smb_tex::PACKAGE_HEADER_SIZE as usize + smb_tex::TEX_HEADER_SIZE as usize + 32;
+    data[field..field + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+
+    let err = remove_textures(&data, &[1]).unwrap_err();
+    assert!(matches!(err, TextureError::InvalidDataOffset { id: 2, offset: 0x10000, .. }), "{err}");
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use smb_tex::verify::verify_package;
+use smb_tex::{
+    repack_package, write_texture_package, write_texture_package_with_options, RepackOptions, TextureFormat,
+    TexturePackage, WriteOptions, PACKAGE_HEADER_SIZE, TEX_HEADER_SIZE,
+};
+
+fn package(ids: &[u32]) -> TexturePackage {
+    // the mip levels of the 4x4 textures are 2x2 and 1x1
+    common::package(ids, TextureFormat::R5G6B5, 3)
+}
+
+#[test]
+fn repacked_textures_are_sorted_and_packed_tightly() {
+    let gappy = write_texture_package_with_options(&package(&[3, 1, 2]), &WriteOptions {
+        align: 64,
+        ..WriteOptions::default()
+    }).unwrap();
+
+    let result = repack_package(&gappy, &RepackOptions::default()).unwrap();
+    assert_eq!(verify_package(&result).unwrap(), []);
+    assert_eq!(result, write_texture_package(&package(&[1, 2, 3])).unwrap());
+
+    let options = RepackOptions { keep_order: true, ..RepackOptions::default() };
+    let result = repack_package(&gappy, &options).unwrap();
+    assert_eq!(result, write_texture_package(&package(&[3, 1, 2])).unwrap());
+}
+
+#[test]
+fn shared_data_is_stored_once() {
+    let mut data = write_texture_package(&package(&[2, 1])).unwrap();
+    // point the second header at the data of the first texture
+    let offset_field = |index: usize| {
+        let start = PACKAGE_HEADER_SIZE as usize + index * TEX_HEADER_SIZE as usize + 32;
+        start..start + 4
+    };
+    let first_offset = data[offset_field(0)].to_vec();
+    data[offset_field(1)].copy_from_slice(&first_offset);
+
+    let result = repack_package(&data, &RepackOptions::default()).unwrap();
+    let headers = smb_tex::read_texture_headers(&result).unwrap();
+    assert_eq!(headers.entries[0].data_offset, headers.entries[1].data_offset);
+    assert_eq!(result.len(), (PACKAGE_HEADER_SIZE + 2 * TEX_HEADER_SIZE) as usize + (16 + 4 + 1) * 2);
+}
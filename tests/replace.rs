@@ -0,0 +1,57 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::{read_texture_package, replace_texture, EncodeOptions, TextureError, TextureFormat};
+
+#[test]
+fn replace_overwrites_data_in_place() {
+    let package = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    let image = RgbaImage::from_pixel(2, 1, Rgba([10, 20, 30, 40]));
+
+    let result = replace_texture(&package, 0x1234, &image, &EncodeOptions::default()).unwrap();
+    assert_eq!(result.len(), package.len());
+    assert_eq!(&result[..0x20 + 36], &package[..0x20 + 36]);
+    assert_eq!(&result[0x20 + 36..], &[10, 20, 30, 40, 10, 20, 30, 40]);
+}
+
+#[test]
+fn replace_with_other_size_moves_following_textures() {
+    // two textures, the second one's data following the first one's
+    let mut package = Vec::new();
+    package.extend_from_slice(&2u32.to_le_bytes());
+    package.extend_from_slice(&0x20u32.to_le_bytes());
+    package.resize(0x20, 0);
+    let data_start = 0x20 + 2 * 36;
+    for (id, offset) in [(1, data_start), (2, data_start + 8)] {
+        for field in [id, 2, 1, 0, 0, 0, 0, TextureFormat::R8G8B8A8 as u32, offset] {
+            package.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    package.extend_from_slice(&[1; 8]);
+    package.extend_from_slice(&[2; 8]);
+    let image = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 40]));
+
+    let result = replace_texture(&package, 1, &image, &EncodeOptions::default()).unwrap();
+    assert_eq!(result.len(), package.len() + 8);
+    let tp = read_texture_package(&result).unwrap();
+    assert_eq!(tp.textures[0].data, image);
+    assert_eq!(tp.textures[1].data, RgbaImage::from_pixel(2, 1, Rgba([2; 4])));
+}
+
+#[test]
+fn replace_unknown_id_fails() {
+    let package = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    let image = RgbaImage::new(2, 1);
+
+    let err = replace_texture(&package, 0x4321, &image, &EncodeOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::TextureNotFound(0x4321)), "{err}");
+}
+
+#[test]
+fn replace_with_empty_image_fails() {
+    let package = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1; 8]);
+    let image = RgbaImage::new(0, 0);
+
+    let err = replace_texture(&package, 0x1234, &image, &EncodeOptions::default()).unwrap_err();
+    assert!(matches!(err, TextureError::EmptyTexture(0x1234)), "{err}");
+}
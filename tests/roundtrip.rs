@@ -0,0 +1,297 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use smb_tex::texture::image_hash;
+use smb_tex::{
+    decode_texture, encode_texture, load_texture, read_texture_headers, read_texture_headers_from, read_texture_package,
+    read_texture_package_from, read_texture_package_with_options, write_texture_package, write_texture_package_with_options,
+    ChannelOrder, EncodeOptions, RawTextureData, ReadOptions, Texture, TextureDataArgs, TextureFormat, TextureMeta,
+    TexturePackage, WriteOptions,
+};
+use smb_tex::verify::verify_package_with_options;
+
+/// A small non-symmetric image, so that a wrong vertical flip is caught
+fn test_image() -> RgbaImage {
+    RgbaImage::from_fn(5, 3, |x, y| {
+        Rgba([
+            (x * 60) as u8,
+            (y * 120) as u8,
+            ((x + y) * 35) as u8,
+            if (x + y) % 2 == 0 { 0xFF } else { (x * 50) as u8 },
+        ])
+    })
+}
+
+fn round_trip(image: &RgbaImage, format: TextureFormat) -> RgbaImage {
+    let data = write_texture_package(&common::single_texture(image.clone(), format)).unwrap();
+    let mut tp = read_texture_package(&data).unwrap();
+    assert_eq!(tp.textures.len(), 1);
+    tp.textures.remove(0).data
+}
+
+/// Checks that each channel of the result is within the given tolerance of the source
+fn assert_close(source: &RgbaImage, result: &RgbaImage, tolerance: [u8; 4]) {
+    assert_eq!(source.dimensions(), result.dimensions());
+    for ((x, y, expected), actual) in source.enumerate_pixels().zip(result.pixels()) {
+        for c in 0..4 {
+            let diff = expected[c].abs_diff(actual[c]);
+            assert!(
+                diff <= tolerance[c],
+                "channel {c} of pixel ({x}, {y}): expected {expected:?}, got {actual:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn r8g8b8a8_is_exact() {
+    let image = test_image();
+    assert_eq!(round_trip(&image, TextureFormat::R8G8B8A8), image);
+}
+
+#[test]
+fn r5g6b5_forces_opaque_alpha() {
+    let image = test_image();
+    let result = round_trip(&image, TextureFormat::R5G6B5);
+    assert!(result.pixels().all(|p| p[3] == 0xFF));
+    assert_close(&image, &result, [8, 4, 8, 0xFF]);
+}
+
+#[test]
+fn r5g5b5a1_round_trips() {
+    let image = test_image();
+    assert_close(&image, &round_trip(&image, TextureFormat::R5G5B5A1), [8, 8, 8, 127]);
+}
+
+#[test]
+fn r4g4b4a4_round_trips() {
+    let image = test_image();
+    assert_close(&image, &round_trip(&image, TextureFormat::R4G4B4A4), [17, 17, 17, 17]);
+}
+
+#[test]
+fn single_texture_encode_decode() {
+    let image = test_image();
+    let data = encode_texture(&image, TextureFormat::R8G8B8A8).unwrap();
+    assert_eq!(data.len(), 5 * 3 * 4);
+    let args = TextureDataArgs {
+        width: 5,
+        height: 3,
+        texture_format: TextureFormat::R8G8B8A8,
+        no_flip: false,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    assert_eq!(decode_texture(&data, &args).unwrap(), image);
+    assert!(decode_texture(&data[1..], &args).is_err());
+}
+
+#[test]
+fn unedited_texture_keeps_raw_data() {
+    let dir = common::scratch_dir("unedited_texture_keeps_raw_data");
+    let image = test_image();
+    // values that a decode/encode cycle does not preserve
+    let raw: Vec<u8> = (0..image.width() * image.height() * 2).map(|v| (v * 7) as u8).collect();
+    let meta = TextureMeta {
+        image_hash: Some(image_hash(&image)),
+        ..common::meta(1, TextureFormat::R5G6B5)
+    };
+    common::write_texture_files(&dir, "a", &meta, &image);
+    std::fs::write(dir.join("a.bin"), &raw).unwrap();
+
+    let tp = TexturePackage::from_directory(&dir).unwrap();
+    let options = EncodeOptions::default();
+    assert_eq!(tp.textures[0].encode(&options).unwrap(), raw);
+
+    let mut edited = image.clone();
+    edited.put_pixel(0, 0, Rgba([1, 2, 3, 4]));
+    common::write_texture_files(&dir, "a", &meta, &edited);
+    let tp = TexturePackage::from_directory(&dir).unwrap();
+    assert!(tp.textures[0].raw.is_none());
+
+    common::write_texture_files(&dir, "a", &meta, &image);
+    let mut tp = TexturePackage::from_directory(&dir).unwrap();
+    tp.textures[0].meta.texture_format = TextureFormat::R4G4B4A4;
+    assert_ne!(tp.textures[0].encode(&options).unwrap(), raw);
+}
+
+#[test]
+fn no_flip_keeps_rows_top_down() {
+    let image = test_image();
+    let mut tp = common::single_texture(image.clone(), TextureFormat::R8G8B8A8);
+    tp.textures[0].meta.no_flip = true;
+    let data = tp.textures[0].encode(&EncodeOptions::default()).unwrap();
+    assert_eq!(data, image.as_raw().as_slice());
+
+    let args = TextureDataArgs {
+        width: 5,
+        height: 3,
+        texture_format: TextureFormat::R8G8B8A8,
+        no_flip: true,
+        channel_order: ChannelOrder::Rgba,
+        row_stride: None,
+    };
+    assert_eq!(decode_texture(&data, &args).unwrap(), image);
+}
+
+#[test]
+fn package_is_read_from_a_file() {
+    let dir = common::scratch_dir("package_is_read_from_a_file");
+    let image = test_image();
+    let path = dir.join("package.tpg");
+    std::fs::write(&path, write_texture_package(&common::single_texture(image.clone(), TextureFormat::R8G8B8A8)).unwrap()).unwrap();
+
+    let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+    let tp = read_texture_package_from(file).unwrap();
+    assert_eq!(tp.textures.len(), 1);
+    assert_eq!(tp.textures[0].data, image);
+}
+
+#[test]
+fn single_texture_is_loaded_on_demand() {
+    let image = test_image();
+    let package = write_texture_package(&common::single_texture(image.clone(), TextureFormat::R4G4B4A4)).unwrap();
+
+    let mut reader = std::io::Cursor::new(&package);
+    let headers = read_texture_headers_from(&mut reader).unwrap();
+    let texture = load_texture(&mut reader, &headers.entries[0]).unwrap();
+    assert_eq!(texture.meta.id, 0x1234);
+    assert_eq!(texture.data, read_texture_package(&package).unwrap().textures[0].data);
+}
+
+#[test]
+fn texture_round_trips_through_png_bytes() {
+    let tp = common::single_texture(test_image(), TextureFormat::R8G8B8A8);
+    let png = tp.textures[0].to_png_bytes().unwrap();
+
+    let texture = Texture::from_png_bytes(tp.textures[0].meta, &png).unwrap();
+    assert_eq!(texture.meta.id, 0x1234);
+    assert_eq!(texture.data, test_image());
+    assert!(Texture::from_png_bytes(tp.textures[0].meta, b"not a png").is_err());
+}
+
+#[test]
+fn built_package_round_trips() {
+    let tp = TexturePackage::builder()
+        .add_texture(0x10, test_image(), TextureFormat::R8G8B8A8)
+        .add_texture_with_meta(TextureMeta { id: 0x11, unk_c: 1, mip_count: 2, ..TextureMeta::default() }, RgbaImage::new(4, 4))
+        .build()
+        .unwrap();
+    assert_eq!(tp.textures[1].mips.len(), 1);
+
+    let read = read_texture_package(&write_texture_package(&tp).unwrap()).unwrap();
+    let ids: Vec<_> = read.textures.iter().map(|t| t.meta.id).collect();
+    assert_eq!(ids, [0x10, 0x11]);
+    assert_eq!(read.textures[0].data, test_image());
+    assert_eq!(read.textures[0].meta.unk_c, 0);
+    assert_eq!(read.textures[1].meta.unk_c, 1);
+    assert_eq!(read.textures[1].meta.texture_format, TextureFormat::R8G8B8A8);
+}
+
+#[test]
+fn empty_package_round_trips() {
+    let data = write_texture_package(&TexturePackage::builder().build().unwrap()).unwrap();
+    assert_eq!(data.len(), smb_tex::PACKAGE_HEADER_SIZE as usize);
+    assert_eq!(smb_tex::verify::verify_package(&data).unwrap(), []);
+
+    let tp = read_texture_package(&data).unwrap();
+    assert!(tp.textures.is_empty());
+    assert_eq!(write_texture_package(&tp).unwrap(), data);
+    assert_eq!(smb_tex::repack_package(&data, &Default::default()).unwrap(), data);
+
+    // a count and a pointer to the end of the file is enough as well
+    let minimal = [0, 0, 0, 0, 8, 0, 0, 0];
+    assert!(read_texture_package(&minimal).unwrap().textures.is_empty());
+    assert_eq!(smb_tex::repack_package(&minimal, &Default::default()).unwrap(), minimal);
+}
+
+#[test]
+fn unknown_header_fields_survive_meta_files() {
+    use smb_tex::meta::{read_meta, write_meta, MetaFormat};
+
+    // offset of unk_c in the header of the only texture, the unk fields are the 16 bytes from there
+    let unk_offset = smb_tex::PACKAGE_HEADER_SIZE as usize + 12;
+    // known and unknown wrap modes, negative values and high flag bits
+    for fields in [[1, 2, -1, 0x10], [7, -3, i32::MIN, 0x8000_0001u32 as i32], [i32::MAX, 0x1234_5678, 5, -1]] {
+        let mut data = common::single_texture_package(2, 1, TextureFormat::R8G8B8A8, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        for (i, field) in fields.iter().enumerate() {
+            data[unk_offset + i * 4..][..4].copy_from_slice(&field.to_le_bytes());
+        }
+        let unk_bytes = data[unk_offset..unk_offset + 16].to_vec();
+
+        for pretty in [false, true] {
+            for format in [MetaFormat::Json, MetaFormat::Toml] {
+                let mut tp = read_texture_package(&data).unwrap();
+                let meta = write_meta(&tp.textures[0].meta, pretty, format);
+                tp.textures[0].meta = read_meta(meta.as_bytes(), format).unwrap();
+                let written = write_texture_package(&tp).unwrap();
+                assert_eq!(written[unk_offset..unk_offset + 16], unk_bytes, "{fields:?} through {meta}");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "package-serde")]
+#[test]
+fn package_round_trips_through_serde() {
+    let meta = TextureMeta {
+        mip_count: 2,
+        ..common::meta(0x30, TextureFormat::R4G4B4A4)
+    };
+    let image = RgbaImage::from_fn(4, 2, |x, y| Rgba([x as u8 * 60, y as u8 * 200, 7, x as u8 * 80]));
+    let built = TexturePackage::builder()
+        .add_texture_with_meta(meta, image)
+        .add_texture(0x0a, RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 4])), TextureFormat::R8G8B8A8)
+        .build()
+        .unwrap();
+    // read back, so that the package has a textures_ptr
+    let mut tp = read_texture_package(&write_texture_package(&built).unwrap()).unwrap();
+    tp.textures[0].mips = built.textures[0].mips.clone();
+    tp.textures[1].raw = Some(smb_tex::RawTextureData {
+        texture_format: TextureFormat::R8G8B8A8,
+        data: vec![1, 2, 3, 4],
+    });
+
+    let json = serde_json::to_string(&tp).unwrap();
+    let read: TexturePackage = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.textures_ptr, tp.textures_ptr);
+    for (read, texture) in read.textures.iter().zip(tp.textures.iter()) {
+        assert_eq!(serde_json::to_value(read.meta).unwrap(), serde_json::to_value(texture.meta).unwrap());
+        assert_eq!(read.data, texture.data);
+        assert_eq!(read.mips, texture.mips);
+        assert_eq!(read.raw.as_ref().map(|r| &r.data), texture.raw.as_ref().map(|r| &r.data));
+    }
+    assert_eq!(write_texture_package(&read).unwrap(), write_texture_package(&tp).unwrap());
+}
+
+#[test]
+fn padded_rows_round_trip_through_a_package() {
+    let image = test_image();
+    let mut tp = common::single_texture(image.clone(), TextureFormat::R8G8B8A8);
+    let mip = image::imageops::resize(&image, 2, 1, image::imageops::FilterType::Nearest);
+    tp.textures[0].mips.push(mip);
+    // the raw data is packed tightly and has to be padded when written
+    let raw = encode_texture(&image, TextureFormat::R5G6B5).unwrap();
+    tp.textures.push(Texture {
+        meta: common::meta(0x5678, TextureFormat::R5G6B5),
+        data: decode_texture(&raw, &TextureDataArgs { width: 5, height: 3, texture_format: TextureFormat::R5G6B5, no_flip: false, channel_order: ChannelOrder::Rgba, row_stride: None }).unwrap(),
+        mips: Vec::new(),
+        raw: Some(RawTextureData { texture_format: TextureFormat::R5G6B5, data: raw.clone() }),
+    });
+
+    let write_options = WriteOptions { row_align: 128, ..WriteOptions::default() };
+    let data = write_texture_package_with_options(&tp, &write_options).unwrap();
+    let headers = read_texture_headers(&data).unwrap();
+    let (first, second) = (&headers.entries[0], &headers.entries[1]);
+    // three rows of the base level and one of the mip level, each padded to 128 bytes
+    assert_eq!(second.data_offset - first.data_offset, 4 * 128);
+    assert_eq!(data.len() as u32 - second.data_offset, 3 * 128);
+    assert_eq!(second.raw_data_with_row_align(&data, 128).unwrap()[..10], raw[..10]);
+
+    let options = ReadOptions { row_align: 128, ..ReadOptions::default() };
+    assert!(verify_package_with_options(&data, &options).unwrap().is_empty());
+    let read = read_texture_package_with_options(&data, &options).unwrap();
+    assert_eq!(read.textures.len(), 2);
+    assert_eq!(read.textures[0].data, image);
+    assert_eq!(read.textures[1].data, tp.textures[1].data);
+}
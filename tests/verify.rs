@@ -0,0 +1,50 @@
+use smb_tex::verify::{checksum, verify_package, Problem};
+
+/// Builds a package from (id, width, height, data offset) R8G8B8A8 texture headers
+fn package(textures: &[(u32, u32, u32, u32)], size: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(textures.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&0x20u32.to_le_bytes());
+    buf.resize(0x20, 0);
+    for &(id, width, height, offset) in textures {
+        for field in [id, width, height, 0, 0, 0, 0, 3, offset] {
+            buf.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    buf.resize(size, 0);
+    buf
+}
+
+#[test]
+fn valid_package_has_no_problems() {
+    let data = package(&[(1, 2, 2, 0x68), (2, 1, 1, 0x78)], 0x7C);
+    assert_eq!(verify_package(&data).unwrap(), []);
+}
+
+#[test]
+fn all_problems_are_reported() {
+    let data = package(&[(1, 2, 2, 0xB0), (1, 0, 4, 0xC0), (3, 2, 2, 0xB4), (4, 1, 1, 0x20)], 0xC2);
+    let problems = verify_package(&data).unwrap();
+    assert_eq!(
+        problems,
+        [
+            Problem::DuplicateId { id: 1, first_index: 0, index: 1 },
+            Problem::ZeroDimensions { id: 1, width: 0, height: 4 },
+            Problem::OutOfBounds { id: 3, offset: 0xB4, size: 16, file_size: 0xC2 },
+            Problem::NotIncreasing { id: 3, offset: 0xB4, previous_id: 1, previous_offset: 0xC0 },
+            Problem::OverlapsHeaders { id: 4, offset: 0x20 },
+            Problem::NotIncreasing { id: 4, offset: 0x20, previous_id: 3, previous_offset: 0xB4 },
+        ][..]
+    );
+}
+
+#[test]
+fn checksum_is_crc32() {
+    assert_eq!(checksum(&b""[..]).unwrap(), 0);
+    assert_eq!(checksum(&b"123456789"[..]).unwrap(), 0xCBF4_3926);
+
+    let data = package(&[(1, 2, 2, 0x68)], 0x78);
+    let mut corrupted = data.clone();
+    corrupted[0x70] ^= 1;
+    assert_ne!(checksum(data.as_slice()).unwrap(), checksum(corrupted.as_slice()).unwrap());
+}